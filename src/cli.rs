@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{ArgGroup, Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -23,6 +24,19 @@ pub struct CliArgs {
     )]
     pub state: PathBuf,
 
+    /// Record all forge API responses as fixtures to this directory
+    #[arg(
+        long,
+        value_name = "DIR",
+        global = true,
+        conflicts_with = "replay_fixtures"
+    )]
+    pub record_fixtures: Option<PathBuf>,
+
+    /// Replay forge API responses from fixtures in this directory instead of accessing the network
+    #[arg(long, value_name = "DIR", global = true)]
+    pub replay_fixtures: Option<PathBuf>,
+
     // /// Turn debugging information on
     // #[arg(short, long, action = clap::ArgAction::Count)]
     // pub verbose: u8,
@@ -34,19 +48,124 @@ pub struct CliArgs {
 pub enum Commands {
     Render(Render),
     AddPr(AddPr),
+    AddCommits(AddCommits),
+    Archive(Archive),
+    Release(Release),
+    CheckPr(CheckPr),
+    Merge(Merge),
+    Add(Add),
+    Remove(Remove),
+    Move(Move),
 }
 
 #[derive(Args)]
 pub struct Render {
+    /// Version to render, renders the unreleased changes if the version was not released yet
     pub version: Option<String>,
+    /// Render the unreleased changes followed by all past releases
+    #[arg(long, conflicts_with = "version")]
+    pub all: bool,
 }
 
 #[derive(Args)]
+#[command(group(ArgGroup::new("since").args(["since_timestamp", "since_ref"])))]
 pub struct AddPr {
-    #[arg(long, conflicts_with = "ids", conflicts_with = "since_ref")]
+    #[arg(long)]
     pub since_timestamp: Option<u64>,
-    #[arg(long, conflicts_with = "ids", conflicts_with = "since_timestamp")]
+    #[arg(long)]
     pub since_ref: Option<String>,
-    #[arg(conflicts_with = "since_ref", conflicts_with = "since_timestamp")]
+    /// Only add PRs merged before this timestamp (defaults to now)
+    #[arg(long, requires = "since")]
+    pub until_timestamp: Option<u64>,
+    #[arg(conflicts_with = "since")]
+    pub prs: Vec<u64>,
+    /// Update the changes previously generated from the PRs instead of adding new changes,
+    /// changes that were edited by hand are never modified
+    #[arg(long)]
+    pub sync: bool,
+}
+
+/// Add changes for all commits since a git ref without accessing the forge
+#[derive(Args)]
+pub struct AddCommits {
+    pub since_ref: String,
+}
+
+/// Move the unreleased changes into a new release
+#[derive(Args)]
+pub struct Archive {
+    pub version: String,
+    /// Release date (defaults to today)
+    #[arg(long)]
+    pub date: Option<NaiveDate>,
+}
+
+/// Add the unreleased changes to the changelog file, bump versions and create a release commit and tag
+#[derive(Args)]
+pub struct Release {
+    pub version: String,
+    /// Version to replace in the version files (defaults to the latest tag)
+    #[arg(long)]
+    pub prev_version: Option<String>,
+    /// Only update the files, don't create a commit and tag
+    #[arg(long)]
+    pub no_commit: bool,
+}
+
+/// Check that a PR produces valid changelog entries without modifying the changelog
+#[derive(Args)]
+pub struct CheckPr {
+    /// PR to check, read from the event payload at `GITHUB_EVENT_PATH` if omitted
+    pub pr: Option<u64>,
+}
+
+/// Merge unreleased changes that describe the same change into a single change
+#[derive(Args)]
+pub struct Merge {
+    /// Changes to merge, either by their position in the statefile (starting at 1)
+    /// or `#<PR>` for all changes of a PR
+    #[arg(required_unless_present = "suggest")]
+    pub changes: Vec<String>,
+    /// Message of the merged change (defaults to the message of the first change)
+    #[arg(long, conflicts_with = "suggest")]
+    pub message: Option<String>,
+    /// Group of the merged change, required if the changes are in different groups
+    #[arg(long, conflicts_with = "suggest")]
+    pub group: Option<String>,
+    /// Only list changes in the same group with similar messages that could be merged
+    #[arg(long)]
+    pub suggest: bool,
+}
+
+/// Add an unreleased change by hand
+#[derive(Args)]
+pub struct Add {
+    pub message: String,
+    /// Group of the change, must be listed in `groups` (if configured)
+    #[arg(long)]
+    pub group: String,
+    /// PRs that implement the change
+    #[arg(long = "pr", value_name = "PR")]
     pub prs: Vec<u64>,
 }
+
+/// Remove unreleased changes
+#[derive(Args)]
+pub struct Remove {
+    /// Changes to remove, either by their position in the statefile (starting at 1)
+    /// or `#<PR>` for all changes of a PR
+    #[arg(required = true)]
+    pub changes: Vec<String>,
+}
+
+/// Move unreleased changes to another group
+#[derive(Args)]
+pub struct Move {
+    /// Changes to move, either by their position in the statefile (starting at 1)
+    /// or `#<PR>` for all changes of a PR
+    #[arg(required = true)]
+    pub changes: Vec<String>,
+    /// The new group, must be listed in `groups` (if configured)
+    #[arg(long)]
+    pub group: String,
+}
@@ -1,6 +1,10 @@
+use anyhow::{bail, Result};
 use indexmap::IndexSet;
-use serde::{Deserialize, Serialize};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::PathBuf;
 
 #[derive(Deserialize, Serialize)]
 pub struct Config {
@@ -8,6 +12,14 @@ pub struct Config {
     #[serde(default)]
     pub changelog_branch: String,
     pub repo: String,
+    #[serde(default)]
+    pub forge: ForgeKind,
+    /// Base url of the forge API, defaults to the public instance of the forge
+    pub api_url: Option<String>,
+    /// Base url of the forge web interface used for links, derived from `api_url` if not set
+    pub web_url: Option<String>,
+    /// API token, only used if the token is not set in the environment
+    pub token: Option<String>,
     pub default_group: Option<String>,
     pub release_pr_label: Option<String>,
     #[serde(default)]
@@ -15,10 +27,188 @@ pub struct Config {
     pub template: String,
     #[serde(default)]
     pub groups: IndexSet<String>,
+    /// What to do with changelog lines that use a group which is not listed in `groups`
+    #[serde(default)]
+    pub unknown_groups: UnknownGroups,
     #[serde(default)]
     pub ignored_labels: IndexSet<String>,
     #[serde(default)]
     pub ignored_authors: IndexSet<String>,
+    /// Ignore all PRs and commits authored by bots
+    #[serde(default)]
+    pub ignore_bots: bool,
     #[serde(default)]
     pub ignored_title_prefix: IndexSet<String>,
+    /// Regular expressions, PRs with a matching title are ignored
+    #[serde(default)]
+    pub ignored_title_patterns: Vec<Pattern>,
+    /// Regular expressions, PRs with a matching description are ignored
+    #[serde(default)]
+    pub ignored_body_patterns: Vec<Pattern>,
+    /// Categorize changes by the conventional commit prefix of their title
+    pub conventional_commits: Option<ConventionalCommits>,
+    /// Record the authors whose first PR is part of the release (only supported for GitHub)
+    #[serde(default)]
+    pub detect_new_contributors: bool,
+    #[serde(default)]
+    pub api_limits: ApiLimits,
+    #[serde(default = "default_changelog_file")]
+    pub changelog_file: PathBuf,
+    #[serde(default)]
+    pub tag_prefix: String,
+    #[serde(default)]
+    pub version_files: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct ConventionalCommits {
+    /// Maps conventional commit types (like `feat`) to groups
+    #[serde(default)]
+    pub types: HashMap<String, String>,
+    /// Maps conventional commit scopes to groups, takes priority over `types`
+    #[serde(default)]
+    pub scopes: HashMap<String, String>,
+    /// Group for breaking changes (marked with `!` or a `BREAKING CHANGE:` footer)
+    pub breaking_group: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownGroups {
+    /// Keep the group and print a warning
+    #[default]
+    Warn,
+    /// Fail instead of adding the change
+    Error,
+    /// Add the change to the `default_group` instead
+    Default,
+}
+
+/// A regular expression in the config
+pub struct Pattern(Regex);
+
+impl Deref for Pattern {
+    type Target = Regex;
+
+    fn deref(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct ApiLimits {
+    /// How often a failed API call is retried
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub initial_backoff_ms: u64,
+    /// Longest time to wait for a rate limit to reset before giving up
+    pub max_rate_limit_wait_secs: u64,
+}
+
+impl Default for ApiLimits {
+    fn default() -> ApiLimits {
+        ApiLimits {
+            max_retries: 5,
+            initial_backoff_ms: 1000,
+            max_rate_limit_wait_secs: 3600,
+        }
+    }
+}
+
+impl Config {
+    /// Groups are only validated if the config lists them
+    pub fn is_known_group(&self, group: &str) -> bool {
+        self.groups.is_empty() || self.groups.contains(group)
+    }
+
+    /// Base url of the web interface, Gitea has no public instance to default to
+    pub fn web_url(&self) -> Result<String> {
+        if let Some(web_url) = &self.web_url {
+            return Ok(web_url.trim_end_matches('/').to_owned());
+        }
+        let api_url = self.api_url.as_deref().map(|url| url.trim_end_matches('/'));
+        let web_url = match (self.forge, api_url) {
+            (ForgeKind::GitHub, None | Some("https://api.github.com")) => "https://github.com",
+            (ForgeKind::GitLab, None) => "https://gitlab.com",
+            (ForgeKind::Gitea, None) => bail!("api_url or web_url must be configured for gitea"),
+            // GitHub Enterprise serves its API at `https://HOST/api`
+            (ForgeKind::GitHub, Some(api_url)) => api_url.trim_end_matches("/api"),
+            (ForgeKind::GitLab, Some(api_url)) => api_url.trim_end_matches("/api/v4"),
+            (ForgeKind::Gitea, Some(api_url)) => api_url.trim_end_matches("/api/v1"),
+        };
+        Ok(web_url.to_owned())
+    }
+}
+
+fn default_changelog_file() -> PathBuf {
+    "CHANGELOG.md".into()
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    #[default]
+    GitHub,
+    GitLab,
+    #[serde(alias = "forgejo")]
+    Gitea,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn web_url(config: &str) -> Result<String> {
+        let config =
+            format!("main_branch = 'master'\nrepo = 'helix-editor/helix'\ntemplate = ''\n{config}");
+        toml::from_str::<Config>(&config).unwrap().web_url()
+    }
+
+    #[test]
+    fn derive_web_url() {
+        assert_eq!(web_url("").unwrap(), "https://github.com");
+        let github = "api_url = 'https://api.github.com/'";
+        assert_eq!(web_url(github).unwrap(), "https://github.com");
+        let enterprise = "api_url = 'https://github.example.com/api'";
+        assert_eq!(web_url(enterprise).unwrap(), "https://github.example.com");
+        assert_eq!(web_url("forge = 'gitlab'").unwrap(), "https://gitlab.com");
+        let gitlab = "forge = 'gitlab'\napi_url = 'https://gitlab.example.com/api/v4'";
+        assert_eq!(web_url(gitlab).unwrap(), "https://gitlab.example.com");
+        let gitea = "forge = 'gitea'\napi_url = 'https://codeberg.org/api/v1/'";
+        assert_eq!(web_url(gitea).unwrap(), "https://codeberg.org");
+    }
+
+    #[test]
+    fn explicit_web_url() {
+        let config =
+            "api_url = 'https://api.example.com/graphql'\nweb_url = 'https://example.com/'";
+        assert_eq!(web_url(config).unwrap(), "https://example.com");
+        let gitea = "forge = 'gitea'\nweb_url = 'https://codeberg.org'";
+        assert_eq!(web_url(gitea).unwrap(), "https://codeberg.org");
+    }
+
+    #[test]
+    fn gitea_requires_url() {
+        let err = web_url("forge = 'gitea'").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "api_url or web_url must be configured for gitea"
+        );
+    }
 }
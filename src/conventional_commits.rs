@@ -0,0 +1,146 @@
+use crate::config::ConventionalCommits;
+
+/// A title of the form `type(scope)!: description`
+pub struct ConventionalTitle<'a> {
+    pub kind: &'a str,
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub description: &'a str,
+}
+
+/// The types from the conventional commits specification and the angular convention
+const STANDARD_TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
+impl<'a> ConventionalTitle<'a> {
+    /// Parses a conventional commit title, titles are only considered conventional
+    /// if their type is one of the standard types or configured in `types` so that
+    /// titles like `Docs: …` or `helix-term: …` are left alone
+    pub fn parse(title: &'a str, config: &ConventionalCommits) -> Option<ConventionalTitle<'a>> {
+        let (prefix, description) = title.trim().split_once(':')?;
+        let (prefix, breaking) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+        let (kind, scope) = match prefix.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.trim())),
+            None => (prefix, None),
+        };
+
+        let is_word = |word: &str| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if !is_word(kind) || scope == Some("") {
+            return None;
+        }
+        if !STANDARD_TYPES.contains(&kind) && !config.types.contains_key(kind) {
+            return None;
+        }
+
+        Some(ConventionalTitle {
+            kind,
+            scope,
+            breaking,
+            description: description.trim(),
+        })
+    }
+
+    /// Returns the group for this change, scopes take priority over types
+    pub fn group<'c>(&self, config: &'c ConventionalCommits, body: &str) -> Option<&'c String> {
+        if self.breaking || has_breaking_footer(body) {
+            if let Some(group) = &config.breaking_group {
+                return Some(group);
+            }
+        }
+        self.scope
+            .and_then(|scope| config.scopes.get(scope))
+            .or_else(|| config.types.get(self.kind))
+    }
+}
+
+fn has_breaking_footer(body: &str) -> bool {
+    body.lines()
+        .any(|line| line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ConventionalCommits {
+        ConventionalCommits {
+            types: [("deps".to_owned(), "Dependencies".to_owned())].into(),
+            ..ConventionalCommits::default()
+        }
+    }
+
+    fn parse(title: &str) -> Option<(&str, Option<&str>, bool, &str)> {
+        let title = ConventionalTitle::parse(title, &config())?;
+        Some((title.kind, title.scope, title.breaking, title.description))
+    }
+
+    #[test]
+    fn type_only() {
+        assert_eq!(
+            parse("feat: add workspace symbol picker"),
+            Some(("feat", None, false, "add workspace symbol picker"))
+        );
+        assert_eq!(
+            parse("deps: update tree-sitter"),
+            Some(("deps", None, false, "update tree-sitter"))
+        );
+    }
+
+    #[test]
+    fn scope() {
+        assert_eq!(
+            parse("fix(lsp): handle empty hover"),
+            Some(("fix", Some("lsp"), false, "handle empty hover"))
+        );
+        assert_eq!(
+            parse("fix( lsp ): handle empty hover"),
+            Some(("fix", Some("lsp"), false, "handle empty hover"))
+        );
+        assert_eq!(parse("fix(): handle empty hover"), None);
+        assert_eq!(parse("fix(lsp: handle empty hover"), None);
+    }
+
+    #[test]
+    fn breaking() {
+        assert_eq!(
+            parse("feat!: remove :sort"),
+            Some(("feat", None, true, "remove :sort"))
+        );
+        assert_eq!(
+            parse("refactor(commands)!: remove :sort"),
+            Some(("refactor", Some("commands"), true, "remove :sort"))
+        );
+    }
+
+    #[test]
+    fn not_conventional() {
+        assert_eq!(parse("Add workspace symbol picker"), None);
+        assert_eq!(parse("Docs: fix typo"), None);
+        assert_eq!(parse("helix-term: fix panic"), None);
+        assert_eq!(parse("Update :sort docs"), None);
+        assert_eq!(parse("feat add picker: lsp"), None);
+    }
+
+    #[test]
+    fn breaking_footer() {
+        let config = ConventionalCommits {
+            breaking_group: Some("Breaking changes".to_owned()),
+            ..config()
+        };
+        let title = ConventionalTitle::parse("feat: remove :sort", &config).unwrap();
+        assert_eq!(
+            title.group(&config, "BREAKING CHANGE: gone"),
+            Some(&"Breaking changes".to_owned())
+        );
+        assert_eq!(title.group(&config, ""), None);
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt::{self, Display};
+
+use anyhow::{bail, Result};
+
+use crate::config::{Config, UnknownGroups};
+use crate::conventional_commits::ConventionalTitle;
+use crate::forge::Label;
+use crate::parser::{self, Directive, Parsed};
+use crate::state::Change;
+use crate::util::edit_distance;
+
+/// The changes generated for a single PR or commit
+pub struct Entries {
+    pub changes: Vec<Change>,
+    /// Malformed changelog lines that were ignored
+    pub problems: Vec<String>,
+    /// Groups of `changelog[group]:` lines that are not listed in `Config::groups`
+    pub unknown_groups: Vec<UnknownGroup>,
+}
+
+impl Entries {
+    /// Prints all problems as warnings, unknown groups are handled according to `Config::unknown_groups`
+    pub fn report(&self, config: &Config, source: &str) -> Result<()> {
+        for problem in &self.problems {
+            eprintln!("warning: {source}: {problem}");
+        }
+        for unknown in &self.unknown_groups {
+            match config.unknown_groups {
+                UnknownGroups::Warn => eprintln!("warning: {source}: {unknown}"),
+                UnknownGroups::Error => bail!("{source}: {unknown}"),
+                UnknownGroups::Default => match &config.default_group {
+                    Some(default) => {
+                        eprintln!("warning: {source}: {unknown}, using default group `{default}`")
+                    }
+                    None => eprintln!("warning: {source}: {unknown}, no default group configured"),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct UnknownGroup {
+    pub name: String,
+    pub suggestion: Option<String>,
+}
+
+impl UnknownGroup {
+    fn new(config: &Config, name: String) -> UnknownGroup {
+        // allow roughly one typo every three characters
+        let max_distance = (name.chars().count() / 3).max(1);
+        let suggestion = config
+            .groups
+            .iter()
+            .chain(config.label_groups.keys())
+            .map(|candidate| (edit_distance(&name, candidate), candidate))
+            .filter(|&(distance, _)| distance <= max_distance)
+            .min()
+            .map(|(_, candidate)| candidate.clone());
+        UnknownGroup { name, suggestion }
+    }
+}
+
+/// Fails (with a suggestion for the intended group) if `group` is not listed in `Config::groups`
+pub fn check_group(config: &Config, group: &str) -> Result<()> {
+    if !config.is_known_group(group) {
+        bail!("{}", UnknownGroup::new(config, group.to_owned()))
+    }
+    Ok(())
+}
+
+impl Display for UnknownGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown group `{}`", self.name)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a PR or commit is not added to the changelog
+pub enum IgnoreReason {
+    Label(String),
+    Author(String),
+    Bot(String),
+    TitlePrefix(String),
+    TitlePattern(String),
+    BodyPattern(String),
+    /// The description contains a `changelog: skip` (or `none`) line
+    Marker(String),
+}
+
+impl Display for IgnoreReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IgnoreReason::Label(label) => write!(f, "has the ignored label `{label}`"),
+            IgnoreReason::Author(author) => write!(f, "authored by the ignored author `{author}`"),
+            IgnoreReason::Bot(author) => write!(f, "authored by the bot `{author}`"),
+            IgnoreReason::TitlePrefix(prefix) => {
+                write!(f, "title starts with the ignored prefix `{prefix}`")
+            }
+            IgnoreReason::TitlePattern(pattern) => {
+                write!(f, "title matches the ignored pattern `{pattern}`")
+            }
+            IgnoreReason::BodyPattern(pattern) => {
+                write!(f, "description matches the ignored pattern `{pattern}`")
+            }
+            IgnoreReason::Marker(line) => write!(f, "opted out with `{line}`"),
+        }
+    }
+}
+
+/// Returns why a PR or commit should not be added to the changelog (if it is ignored).
+/// `author` lists all names the author is known by (the first one is used for messages).
+pub fn ignore_reason(
+    config: &Config,
+    title: &str,
+    body: &str,
+    author: &[&str],
+    is_bot: bool,
+    labels: &[Label],
+) -> Option<IgnoreReason> {
+    if let Some(label) = labels
+        .iter()
+        .find(|label| config.ignored_labels.contains(&label.name))
+    {
+        return Some(IgnoreReason::Label(label.name.clone()));
+    }
+
+    if let Some(author) = author
+        .iter()
+        .find(|&&author| config.ignored_authors.contains(author))
+    {
+        return Some(IgnoreReason::Author((*author).to_owned()));
+    }
+    if config.ignore_bots && is_bot {
+        return Some(IgnoreReason::Bot(author[0].to_owned()));
+    }
+
+    let title = title.trim();
+    if let Some(prefix) = config
+        .ignored_title_prefix
+        .iter()
+        .find(|prefix| title.starts_with(prefix.as_str()))
+    {
+        return Some(IgnoreReason::TitlePrefix(prefix.clone()));
+    }
+    if let Some(pattern) = config
+        .ignored_title_patterns
+        .iter()
+        .find(|pattern| pattern.is_match(title))
+    {
+        return Some(IgnoreReason::TitlePattern(pattern.to_string()));
+    }
+    if let Some(pattern) = config
+        .ignored_body_patterns
+        .iter()
+        .find(|pattern| pattern.is_match(body))
+    {
+        return Some(IgnoreReason::BodyPattern(pattern.to_string()));
+    }
+
+    parser::parse(body)
+        .directives
+        .into_iter()
+        .find(|directive| {
+            directive.group.is_none()
+                && (directive.message.eq_ignore_ascii_case("skip")
+                    || directive.message.eq_ignore_ascii_case("none"))
+        })
+        .map(|directive| IgnoreReason::Marker(directive.line))
+}
+
+/// Generates the changes for a PR or commit from its title, description and labels
+pub fn changelog_entries(config: &Config, title: &str, body: &str, labels: &[Label]) -> Entries {
+    let mut changes = Vec::new();
+    let Parsed {
+        directives,
+        mut problems,
+    } = parser::parse(body);
+    let mut unknown_groups = Vec::new();
+    let mut main_changes = Vec::new();
+    let mut generate_main_change = true;
+    for Directive {
+        group,
+        message,
+        line,
+    } in directives
+    {
+        if message.is_empty() {
+            problems.push(format!("empty message in `{line}`"));
+            continue;
+        }
+        let Some(group) = group else {
+            main_changes.push(message);
+            continue;
+        };
+        let group = match config.label_groups.get(&group) {
+            Some(group) => group.to_owned(),
+            None if !config.is_known_group(&group) => {
+                unknown_groups.push(UnknownGroup::new(config, group.clone()));
+                match (config.unknown_groups, &config.default_group) {
+                    (UnknownGroups::Default, Some(default)) => default.clone(),
+                    _ => group,
+                }
+            }
+            None => group,
+        };
+        changes.push(Change { message, group });
+        generate_main_change = false;
+    }
+
+    let conventional_title = config
+        .conventional_commits
+        .as_ref()
+        .and_then(|conventional| {
+            Some((conventional, ConventionalTitle::parse(title, conventional)?))
+        });
+    let (title, conventional_group) = match conventional_title {
+        Some((conventional, conventional_title)) => (
+            conventional_title.description,
+            conventional_title.group(conventional, body),
+        ),
+        None => (title.trim(), None),
+    };
+
+    // explicit main changes are always added, even if there are other changes
+    if main_changes.is_empty() {
+        main_changes.push(title.to_owned());
+    } else {
+        generate_main_change = true;
+    }
+
+    // labels are set explicitly so they take priority over the conventional commit prefix
+    let mut groups: Vec<_> = labels
+        .iter()
+        .filter_map(|label| config.label_groups.get(&label.name))
+        .collect();
+    if groups.is_empty() {
+        groups.extend(conventional_group);
+    }
+    for main_change in main_changes {
+        for group in &groups {
+            changes.push(Change {
+                message: main_change.clone(),
+                group: group.to_string(),
+            });
+        }
+        if generate_main_change && groups.is_empty() {
+            if let Some(group) = &config.default_group {
+                changes.push(Change {
+                    message: main_change,
+                    group: group.to_owned(),
+                });
+            }
+        }
+    }
+
+    Entries {
+        changes,
+        problems,
+        unknown_groups,
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::{Config, ForgeKind};
+use crate::gitea_api::Gitea;
+use crate::github_api::GitHub;
+use crate::gitlab_api::GitLab;
+use crate::transport::Transport;
+
+/// A pull request (or merge request) independent of the forge it was retrieved from
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<Label>,
+    pub author: Author,
+    /// Issues (in the same repository) that are closed by this pull request
+    pub closed_issues: Vec<u64>,
+    /// Authors from the `Co-authored-by` trailers of the commits in this pull request
+    /// other than the author of the pull request
+    pub co_authors: Vec<Author>,
+    /// When the pull request was merged, `None` if it is still open
+    pub merged_at: Option<DateTime<Utc>>,
+    /// The commit that merged the pull request into the main branch
+    pub merge_commit: Option<String>,
+    /// Link to the pull request in the web interface
+    pub url: String,
+}
+
+impl PullRequest {
+    /// The authors with an account on the forge,
+    /// co-authors that are only known from commit trailers don't have one
+    pub fn accounts(&self) -> impl Iterator<Item = &Author> {
+        let co_authors = self.co_authors.iter();
+        std::iter::once(&self.author).chain(co_authors.filter(|author| author.kind.is_some()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Author {
+    pub login: String,
+    /// Display name
+    #[serde(default, alias = "full_name")]
+    pub name: Option<String>,
+    /// The kind of account (`User`, `Bot`, ...) if reported by the forge,
+    /// co-authors without an account are only known by name and never have a kind
+    #[serde(default, rename = "__typename", alias = "type")]
+    pub kind: Option<String>,
+}
+
+impl Author {
+    /// A co-author that is only known by name
+    pub fn from_name(name: &str) -> Author {
+        Author {
+            login: name.to_owned(),
+            name: None,
+            kind: None,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match &self.name {
+            Some(name) if !name.trim().is_empty() => name.trim(),
+            _ => &self.login,
+        }
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.kind.as_deref() == Some("Bot") || is_bot_name(&self.login)
+    }
+}
+
+/// GitHub apps commit (and appear in REST payloads) as `name[bot]`
+pub fn is_bot_name(name: &str) -> bool {
+    name.ends_with("[bot]")
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Deserialize)]
+#[non_exhaustive]
+pub struct Label {
+    pub name: String,
+}
+
+pub trait Forge {
+    /// Retrieves a single pull request by its number
+    fn lookup_pr(&self, pr: u64) -> Result<PullRequest>;
+
+    /// Retrieves all pull requests that were merged into the main branch after `since`
+    /// (and before `until` if set)
+    fn merged_prs_since(
+        &self,
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PullRequest>>;
+
+    /// Returns the first merged pull request of `author` if it was merged at or before `until`,
+    /// `None` if there is no such pull request or the forge can't look them up
+    /// (only GitHub can, see `Config::detect_new_contributors`)
+    fn first_merged_pr(&self, _author: &str, _until: DateTime<Utc>) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+pub fn from_config<'a>(
+    config: &'a Config,
+    transport: &'a dyn Transport,
+) -> Result<Box<dyn Forge + 'a>> {
+    let forge: Box<dyn Forge> = match config.forge {
+        ForgeKind::GitHub => Box::new(GitHub { config, transport }),
+        ForgeKind::GitLab => Box::new(GitLab { config, transport }),
+        ForgeKind::Gitea => {
+            let api_url = config
+                .api_url
+                .as_deref()
+                .context("api_url must be configured for gitea")?;
+            Box::new(Gitea {
+                config,
+                api_url,
+                transport,
+            })
+        }
+    };
+    Ok(forge)
+}
+
+/// Returns the issues referenced with a closing keyword (like `Fixes #123`) in a PR description,
+/// for forges that don't expose the issues closed by a PR in their API
+pub fn closing_keyword_refs(body: &str) -> Vec<u64> {
+    static KEYWORDS: OnceLock<Regex> = OnceLock::new();
+    let keywords = KEYWORDS.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?\s+#(\d+)\b").unwrap()
+    });
+    keywords
+        .captures_iter(body)
+        .filter_map(|captures| captures[1].parse().ok())
+        .collect()
+}
+
+/// Returns the names in the `Co-authored-by` trailers of a commit message
+pub fn co_authors(message: &str) -> impl Iterator<Item = &str> {
+    message.lines().filter_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if !key.trim().eq_ignore_ascii_case("co-authored-by") {
+            return None;
+        }
+        // strip the email: `Name <email>`
+        let name = value.split('<').next().unwrap_or(value).trim();
+        (!name.is_empty()).then_some(name)
+    })
+}
+
+/// Collects distinct co-authors, excluding `author` (who is credited already)
+pub fn dedup_co_authors(author: &str, co_authors: impl Iterator<Item = Author>) -> Vec<Author> {
+    let mut res: Vec<Author> = Vec::new();
+    for co_author in co_authors {
+        if co_author.login != author
+            && !res.iter().any(|existing| existing.login == co_author.login)
+        {
+            res.push(co_author);
+        }
+    }
+    res
+}
+
+/// Returns the API token from the environment variable `env_var`
+/// or (if that variable is not set) the `token` configured in the config
+pub fn token(config: &Config, env_var: &str) -> Option<String> {
+    std::env::var(env_var).ok().or_else(|| config.token.clone())
+}
@@ -1,236 +1,291 @@
-// pub fn creae_release_pr(token: String) -> anyhow::Result<()> {
-//     let api = pulls::new(&auth);
-//     let params = PullsListParams::new().state("OPEN");
-//     api.list("pascalkuthe", "openvaf", Some(params))?;
-
 use std::fs;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::str::Lines;
 
-use anyhow::bail;
-use chrono::Utc;
-//     Ok(())
-// }
-use serde::Deserialize;
+use anyhow::{bail, Context, Result};
+use toml_edit::{Document, Item, Value};
 use xshell::{cmd, Shell};
 
-fn find_release_pr<'a>(project: &str, pr_list: &'a [PullRequest]) -> Option<&'a PullRequest> {
-    pr_list
-        .iter()
-        .find(|pr| pr.title_contains(project) && pr.has_label("autorelease: pending"))
-}
-
-fn collect_changelog(lines: Lines) -> String {
-    let mut changelog = String::new();
-    for line in lines {
-        if line.trim().starts_with("##") {
-            break;
-        }
-
-        if !line.trim().is_empty() {
-            changelog.push_str(line);
-            changelog.push('\n')
-        }
+/// Returns the heading level if `line` is a markdown heading
+fn heading_level(line: &str) -> Option<usize> {
+    let line = line.trim_start();
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rem = &line[level..];
+    if level != 0 && (rem.is_empty() || rem.starts_with(' ')) {
+        Some(level)
+    } else {
+        None
     }
-    changelog
 }
 
-fn find_unreleased_section(lines: &mut Lines) -> Option<Range<usize>> {
-    let mut line_start = 0;
-    let line_end = loop {
-        let line = lines.next()?;
-        if let Some(rem) = line.trim_start().strip_prefix("##") {
-            if rem.trim_start() == "[unreleased]" {
-                break line_start + line.len();
-            } else {
-                return None;
+/// Finds the byte range of the section (heading included) that starts
+/// at the first heading of `level` for which `pred` returns true.
+/// The section ends at the next heading of the same (or a higher) level.
+fn find_section(content: &str, level: usize, pred: impl Fn(&str) -> bool) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut pos = 0;
+    for line in content.split_inclusive('\n') {
+        match (start, heading_level(line)) {
+            (None, Some(lvl)) if lvl == level && pred(line.trim()[lvl..].trim()) => {
+                start = Some(pos)
             }
+            (Some(start), Some(lvl)) if lvl <= level => return Some(start..pos),
+            _ => (),
         }
-        line_start += line.len() + 1;
-    };
-
-    Some(line_start..line_end)
+        pos += line.len();
+    }
+    start.map(|start| start..content.len())
 }
 
-fn update_changelog(content: &mut String, new_version: &str) -> Option<String> {
-    let mut lines = content.lines();
-    let range = find_unreleased_section(&mut lines)?;
-    let changelog = collect_changelog(lines);
+/// Inserts a rendered release into the (possibly empty) changelog.
+/// If the changelog contains an `[unreleased]` section at the same heading
+/// level as the release it is replaced. Otherwise the release is inserted
+/// before the first previous release (the first heading at the same level that
+/// contains a version number), so that a title like `# Changelog` stays on top.
+pub fn update_changelog(content: &mut String, release: &str) {
+    let mut release = release.trim().to_owned();
+    release.push_str("\n\n");
+    let level = release.lines().find_map(heading_level).unwrap_or(1);
 
-    let date = Utc::now().format("%Y-%m-%d");
-    let new_header = format!("## {new_version} - {date}");
-    content.replace_range(range, &new_header);
+    let is_unreleased = |heading: &str| {
+        heading
+            .trim_matches(|c| c == '[' || c == ']')
+            .eq_ignore_ascii_case("unreleased")
+    };
+    if let Some(range) = find_section(content, level, is_unreleased) {
+        content.replace_range(range, &release);
+        return;
+    }
 
-    Some(changelog)
+    let is_release = |heading: &str| heading.contains(|c: char| c.is_ascii_digit());
+    match find_section(content, level, is_release) {
+        Some(range) => content.insert_str(range.start, &release),
+        None => {
+            if !content.is_empty() && !content.ends_with("\n\n") {
+                content.push_str(if content.ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                });
+            }
+            content.push_str(&release)
+        }
+    }
 }
 
-fn find_latest_version(sh: &Shell, project: &str) -> anyhow::Result<Option<String>> {
-    let tags = cmd!(sh, "git tag -l --sort=-v:refname {project}-*").read()?;
+pub fn find_latest_version(sh: &Shell, tag_prefix: &str) -> Result<Option<String>> {
+    let tags = cmd!(sh, "git tag -l --sort=-v:refname {tag_prefix}*").read()?;
     let latest_tag = tags.lines().next();
     let version = latest_tag
-        .and_then(|tag| tag.strip_prefix(&format!("{project}-")))
+        .and_then(|tag| tag.strip_prefix(tag_prefix))
         .map(str::to_owned);
     Ok(version)
 }
 
-fn create_release_commit(
-    sh: &Shell,
-    project: &str,
+/// Returns the content of all `files` with the package version `old_version`
+/// replaced by `new_version` (see [`replace_version`]).
+/// Fails (without modifying any file) if a file does not contain `old_version`.
+pub fn replace_versions<'a>(
     old_version: &str,
     new_version: &str,
-    files: &[PathBuf],
-) -> anyhow::Result<Option<String>> {
-    cmd!(sh, "git checkout autorelease-{project}").run()?;
-    cmd!(sh, "git reset --hard origin/master").run()?;
-
-    // update changelog
-    let changelog_file = format!("CHANGELOG_{}.md", project.to_uppercase());
-    let changelog_file = sh.current_dir().join(changelog_file);
-    let mut content = fs::read_to_string(&changelog_file)?.replace("\r\n", "\n");
-    let changelog = update_changelog(&mut content, new_version);
-    if changelog.is_none() {
-        return Ok(None);
-    }
-    fs::write(changelog_file, content)?;
-
-    replace_versions(sh, old_version, new_version, false, files)?;
-
-    let commit_message = format!("chore({project}): release v{new_version}");
-    cmd!(sh, "git add --all").run()?;
-    cmd!(sh, "git commit -m {commit_message}").run()?;
-    cmd!(sh, "git push --force --set-upstream origin/{new_version}").run()?;
-    Ok(changelog)
+    files: &'a [PathBuf],
+) -> Result<Vec<(&'a Path, String)>> {
+    files
+        .iter()
+        .map(|file| {
+            let content = fs::read_to_string(file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            match replace_version(file, &content, old_version, new_version)? {
+                Some(content) => Ok((&**file, content)),
+                None => bail!("version {old_version} not found in {}", file.display()),
+            }
+        })
+        .collect()
 }
 
-fn create_release_cycle_commit(
-    sh: &Shell,
-    project: &str,
+/// Replaces the version of the package in a version file, `None` if it is not `old_version`.
+///
+/// Dependencies may have the same version as the package, so in toml files only
+/// the top-level `version` and the `version` of `[package]` and `[workspace.package]`
+/// are replaced, in `Cargo.lock` the versions of the packages that don't come from
+/// a registry (the workspace members). Other files contain the version of the package
+/// before any other version, so only the first occurrence is replaced.
+fn replace_version(
+    file: &Path,
+    content: &str,
     old_version: &str,
     new_version: &str,
-    files: &[PathBuf],
-) -> anyhow::Result<()> {
-    replace_versions(sh, old_version, new_version, true, files)?;
-
-    let new_version = semvar_dev_version(new_version);
-    let commit_message = format!("chore({project}): start development cycle v{new_version}");
-    cmd!(sh, "git add --all").run()?;
-    cmd!(sh, "git commit -m {commit_message}").run()?;
-    cmd!(sh, "git push --force --set-upstream origin/{new_version}").run()?;
-    Ok(())
-}
-
-fn semvar_dev_version(version: &str) -> String {
-    let (major, rem) = version.split_once('.').unwrap();
-    let (minor, rem) = rem.split_once('.').unwrap();
-    let (patch, _) = rem.split_once('.').unwrap();
-    let patch: u32 = patch.parse().unwrap();
-    format!("{major}.{minor}.{}-dev", patch + 1)
-}
-
-fn python_dev_version(version: &str) -> String {
-    let (major, rem) = version.split_once('.').unwrap();
-    let (minor, rem) = rem.split_once('.').unwrap();
-    let (patch, _) = rem.split_once('.').unwrap();
-    let patch: u32 = patch.parse().unwrap();
-    format!("{major}.{minor}.{}dev", patch + 1)
-}
+) -> Result<Option<String>> {
+    let is_toml = file.extension().is_some_and(|ext| ext == "toml")
+        || file.file_name().is_some_and(|name| name == "Cargo.lock");
+    if !is_toml {
+        let replaced = content.replacen(old_version, new_version, 1);
+        return Ok(content.contains(old_version).then_some(replaced));
+    }
 
-fn dev_version(version: &str, file: &Path) -> anyhow::Result<String> {
-    let version = match file.file_name().and_then(|ext| ext.to_str()) {
-        Some("Cargo.toml" | "Cargo.lock") => semvar_dev_version(&version),
-        Some("setup.py") => python_dev_version(&version),
-        _ => bail!("Unkown faile type {}", file.display()),
+    let mut doc: Document = content
+        .parse()
+        .with_context(|| format!("failed to parse {}", file.display()))?;
+    let replace = |item: Option<&mut Item>| {
+        let Some(version) = item.and_then(Item::as_value_mut) else {
+            return false;
+        };
+        if version.as_str() != Some(old_version) {
+            return false;
+        }
+        let decor = version.decor().clone();
+        *version = Value::from(new_version);
+        *version.decor_mut() = decor;
+        true
     };
-    Ok(version)
+    let mut replaced = replace(doc.get_mut("version"));
+    match doc.get_mut("package") {
+        Some(Item::ArrayOfTables(packages)) => {
+            for package in packages.iter_mut() {
+                if !package.contains_key("source") {
+                    replaced |= replace(package.get_mut("version"));
+                }
+            }
+        }
+        Some(package) => replaced |= replace(package.get_mut("version")),
+        None => (),
+    }
+    if let Some(package) = doc
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("package"))
+    {
+        replaced |= replace(package.get_mut("version"));
+    }
+    Ok(replaced.then(|| doc.to_string()))
 }
 
-fn replace_versions(
+pub fn create_release_commit(
     sh: &Shell,
-    old_version: &str,
+    tag: &str,
     new_version: &str,
-    to_devel: bool,
+    changelog_file: &Path,
+    state_file: &Path,
     files: &[PathBuf],
-) -> anyhow::Result<()> {
-    for file in files {
-        let old_version = dev_version(old_version, file)?;
-        let new_version = if to_devel {
-            dev_version(new_version, file)?
-        } else {
-            cmd!(sh, "git checkout HEAD^ -- {file}").run()?;
-            new_version.to_owned()
-        };
-
-        let mut content = fs::read_to_string(file)?;
-        content.replace(&old_version, &new_version);
-        fs::write(file, &content);
-    }
-
+) -> Result<()> {
+    cmd!(sh, "git add {changelog_file} {state_file} {files...}").run()?;
+    let commit_message = format!("chore: release {new_version}");
+    cmd!(sh, "git commit -m {commit_message}").run()?;
+    let tag_message = format!("Release {new_version}");
+    cmd!(sh, "git tag -a {tag} -m {tag_message}").run()?;
     Ok(())
 }
 
-fn list_pull_requests(token: &str) -> anyhow::Result<Vec<PullRequest>> {
-    let response = call_github_api(
-        token,
-        "pulls",
-        &[],
-        &[("base", "master"), ("per_page", "100")],
-    )?
-    .into_string()?;
-    let res = serde_json::from_str(&response)?;
-    Ok(res)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(content: &str, release: &str) -> String {
+        let mut content = content.to_owned();
+        update_changelog(&mut content, release);
+        content
+    }
 
-const GITHUB_API: &str = "https://api.github.com/repos/pascalkuthe/openvaf";
-fn call_github_api(
-    token: &str,
-    endpoint: &str,
-    headers: &[(&str, &str)],
-    queries: &[(&str, &str)],
-) -> anyhow::Result<ureq::Response> {
-    let url = format!("{GITHUB_API}/{endpoint}");
-    let mut request = ureq::get(&url)
-        .set("Accept", "application/vnd.github+json")
-        .set("Authorization", token);
-    for (param, value) in headers.iter() {
-        request = request.set(param, value)
+    #[test]
+    fn empty_changelog() {
+        assert_eq!(update("", "## 0.2.0\n- foo\n"), "## 0.2.0\n- foo\n\n");
+        assert_eq!(
+            update("# Changelog", "## 0.2.0\n- foo"),
+            "# Changelog\n\n## 0.2.0\n- foo\n\n"
+        );
     }
-    for (param, value) in queries.iter() {
-        request = request.query(param, value);
+
+    #[test]
+    fn insert_before_previous_release() {
+        let changelog = "# Changelog\n\n## 0.1.0\n- bar\n";
+        assert_eq!(
+            update(changelog, "## 0.2.0\n- foo"),
+            "# Changelog\n\n## 0.2.0\n- foo\n\n## 0.1.0\n- bar\n"
+        );
     }
-    let res = request.call()?;
-    Ok(res)
-}
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-#[non_exhaustive]
-pub struct PullRequest {
-    pub url: String,
-    pub id: u64,
-    pub title: Option<String>,
-    pub labels: Option<Vec<Label>>,
-}
+    #[test]
+    fn insert_after_title() {
+        let changelog = "# Changelog\n\n# 0.1.0\n- bar\n";
+        assert_eq!(
+            update(changelog, "# 0.2.0\n- foo"),
+            "# Changelog\n\n# 0.2.0\n- foo\n\n# 0.1.0\n- bar\n"
+        );
+        assert_eq!(
+            update("# Changelog\n\nAll notable changes.\n", "# 0.2.0\n- foo"),
+            "# Changelog\n\nAll notable changes.\n\n# 0.2.0\n- foo\n\n"
+        );
+    }
 
-impl PullRequest {
-    pub fn title_contains(&self, substr: &str) -> bool {
-        if let Some(title) = &self.title {
-            title.contains(substr)
-        } else {
-            false
-        }
+    #[test]
+    fn replace_unreleased() {
+        let changelog = "# Changelog\n\n## [Unreleased]\n- wip\n\n## 0.1.0\n- bar\n";
+        assert_eq!(
+            update(changelog, "## 0.2.0\n- foo"),
+            "# Changelog\n\n## 0.2.0\n- foo\n\n## 0.1.0\n- bar\n"
+        );
+        // an unreleased section at a different level is kept
+        let changelog = "# Unreleased\n\n## 0.1.0\n- bar\n";
+        assert_eq!(
+            update(changelog, "## 0.2.0\n- foo"),
+            "# Unreleased\n\n## 0.2.0\n- foo\n\n## 0.1.0\n- bar\n"
+        );
     }
-    pub fn has_label(&self, label: &str) -> bool {
-        if let Some(labels) = &self.labels {
-            labels.iter().any(|it| it.name == label)
-        } else {
-            false
-        }
+
+    fn replace(file: &str, content: &str) -> Option<String> {
+        replace_version(Path::new(file), content, "0.1.0", "0.2.0").unwrap()
     }
-}
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, Deserialize)]
-#[non_exhaustive]
-pub struct Label {
-    pub id: u64,
-    pub name: String,
+    #[test]
+    fn replace_package_version() {
+        let manifest = r#"[package]
+name = "foo"
+version = "0.1.0" # bumped on release
+
+[dependencies]
+bar = "0.1.0"
+baz = { version = "0.1.0" }
+
+[dependencies.qux]
+version = "0.1.0"
+"#;
+        assert_eq!(
+            replace("Cargo.toml", manifest).unwrap(),
+            manifest.replacen("0.1.0", "0.2.0", 1)
+        );
+        let workspace = "[workspace.package]\nversion = \"0.1.0\"\n";
+        assert_eq!(
+            replace("Cargo.toml", workspace).unwrap(),
+            "[workspace.package]\nversion = \"0.2.0\"\n"
+        );
+        assert_eq!(
+            replace("Cargo.toml", "[dependencies]\nbar = \"0.1.0\"\n"),
+            None
+        );
+
+        let lockfile = r#"version = 3
+
+[[package]]
+name = "bar"
+version = "0.1.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = [
+ "bar",
+]
+"#;
+        let expected = lockfile.replace(
+            "name = \"foo\"\nversion = \"0.1.0\"",
+            "name = \"foo\"\nversion = \"0.2.0\"",
+        );
+        assert_eq!(replace("Cargo.lock", lockfile).unwrap(), expected);
+
+        let package_json = r#"{ "version": "0.1.0", "dependencies": { "bar": "0.1.0" } }"#;
+        assert_eq!(
+            replace("package.json", package_json).unwrap(),
+            r#"{ "version": "0.2.0", "dependencies": { "bar": "0.1.0" } }"#
+        );
+    }
 }
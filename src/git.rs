@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use xshell::{cmd, Shell};
+
+pub struct Commit {
+    pub hash: String,
+    /// Name of the author
+    pub author: String,
+    pub email: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Returns all (non-merge) commits in `since_ref..HEAD`, oldest first
+pub fn commits_since(since_ref: &str) -> Result<Vec<Commit>> {
+    let sh = Shell::new()?;
+    let range = format!("{since_ref}..HEAD");
+    // fields are separated by the ASCII unit separator and commits by the record separator
+    let log = cmd!(
+        sh,
+        "git log --reverse --no-merges --format=%H%x1f%an%x1f%ae%x1f%s%x1f%b%x1e {range}"
+    )
+    .read()?;
+    log.split('\x1e')
+        .map(str::trim)
+        .filter(|commit| !commit.is_empty())
+        .map(|commit| {
+            let mut fields = commit.splitn(5, '\x1f');
+            let mut next_field = || fields.next().context("malformed git log output");
+            Ok(Commit {
+                hash: next_field()?.to_owned(),
+                author: next_field()?.to_owned(),
+                email: next_field()?.to_owned(),
+                subject: next_field()?.to_owned(),
+                body: next_field()?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+impl Commit {
+    /// The GitHub login of the author if they commit with their noreply email
+    /// (`login@users.noreply.github.com` or `12345+login@users.noreply.github.com`)
+    pub fn login(&self) -> Option<&str> {
+        let user = self.email.strip_suffix("@users.noreply.github.com")?;
+        let login = user.split_once('+').map_or(user, |(_, login)| login);
+        (!login.is_empty()).then_some(login)
+    }
+}
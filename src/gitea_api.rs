@@ -0,0 +1,157 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::forge::{self, Author, Forge, Label, PullRequest};
+use crate::transport::Transport;
+
+const PAGE_SIZE: usize = 50;
+
+#[derive(Deserialize)]
+pub struct GiteaPullRequest {
+    pub number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub labels: Vec<Label>,
+    pub user: Author,
+    pub base: Branch,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub merge_commit_sha: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    pub html_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct Commit {
+    pub commit: CommitInfo,
+}
+
+#[derive(Deserialize)]
+pub struct CommitInfo {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct Branch {
+    #[serde(rename = "ref")]
+    pub name: String,
+}
+
+impl From<GiteaPullRequest> for PullRequest {
+    fn from(pr: GiteaPullRequest) -> PullRequest {
+        let body = pr.body.unwrap_or_default();
+        PullRequest {
+            number: pr.number,
+            title: pr.title,
+            closed_issues: forge::closing_keyword_refs(&body),
+            body,
+            labels: pr.labels,
+            author: pr.user,
+            co_authors: Vec::new(),
+            merged_at: pr.merged_at,
+            merge_commit: pr.merge_commit_sha,
+            url: pr.html_url,
+        }
+    }
+}
+
+pub struct Gitea<'a> {
+    pub config: &'a Config,
+    pub api_url: &'a str,
+    pub transport: &'a dyn Transport,
+}
+
+impl Gitea<'_> {
+    fn call_api<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        let url = format!(
+            "{}/repos/{}/{endpoint}",
+            self.api_url.trim_end_matches('/'),
+            self.config.repo
+        );
+        let mut request = ureq::get(&url).set("Accept", "application/json");
+        if let Some(token) = forge::token(self.config, "GITEA_TOKEN") {
+            request = request.set("Authorization", &format!("token {token}"));
+        }
+        for (param, value) in query {
+            request = request.query(param, value);
+        }
+        self.transport
+            .send(self.config, "gitea", &request, None)?
+            .json()
+    }
+
+    /// Retrieves the pages of a paginated endpoint until a page is not full
+    /// or `is_last` returns true for a page
+    fn call_api_paginated<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        is_last: impl Fn(&[T]) -> bool,
+    ) -> Result<Vec<T>> {
+        let limit = PAGE_SIZE.to_string();
+        let mut res = Vec::new();
+        for page in 1.. {
+            let page = page.to_string();
+            let mut page_query = query.to_vec();
+            page_query.extend([("limit", &*limit), ("page", &*page)]);
+            let items: Vec<T> = self.call_api(endpoint, &page_query)?;
+            let last_page = items.len() < PAGE_SIZE || is_last(&items);
+            res.extend(items);
+            if last_page {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Converts a PR and retrieves the co-authors of its commits
+    fn pull_request(&self, pr: GiteaPullRequest) -> Result<PullRequest> {
+        let commits: Vec<Commit> = self.call_api_paginated(
+            &format!("pulls/{}/commits", pr.number),
+            &[("stat", "false"), ("files", "false")],
+            |_| false,
+        )?;
+        let mut pr = PullRequest::from(pr);
+        let co_authors = commits
+            .iter()
+            .flat_map(|commit| forge::co_authors(&commit.commit.message))
+            .map(Author::from_name);
+        pr.co_authors = forge::dedup_co_authors(&pr.author.login, co_authors);
+        Ok(pr)
+    }
+}
+
+impl Forge for Gitea<'_> {
+    fn lookup_pr(&self, pr: u64) -> Result<PullRequest> {
+        let pr: GiteaPullRequest = self.call_api(&format!("pulls/{pr}"), &[])?;
+        self.pull_request(pr)
+    }
+
+    fn merged_prs_since(
+        &self,
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PullRequest>> {
+        // PRs are sorted by their last update (most recent first), a PR that
+        // was merged after `since` must also have been updated after `since`
+        let mut res = self.call_api_paginated(
+            "pulls",
+            &[("state", "closed"), ("sort", "recentupdate")],
+            |prs: &[GiteaPullRequest]| prs.iter().any(|pr| pr.updated_at < since),
+        )?;
+        res.retain(|pr| {
+            pr.base.name == self.config.main_branch
+                && pr.merged_at.is_some_and(|merged_at| {
+                    merged_at >= since && until.is_none_or(|until| merged_at <= until)
+                })
+        });
+        res.sort_by_key(|pr| pr.merged_at);
+        res.into_iter().map(|pr| self.pull_request(pr)).collect()
+    }
+}
@@ -1,23 +1,115 @@
-use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
 use indexmap::IndexSet;
 use indoc::formatdoc;
 use serde::{Deserialize, Serialize};
 
+use crate::config::Config;
+use crate::forge::{self, Author, Forge, Label, PullRequest};
+use crate::transport::Transport;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+/// Maximum number of results returned by the search API for a single query
+const MAX_SEARCH_RESULTS: u64 = 1000;
+
+/// The response to a GraphQL query. Queries may partially succeed,
+/// in which case `data` only contains the fields that could be resolved.
 #[derive(Deserialize)]
 pub struct Query<T> {
-    pub data: T,
+    pub data: Option<T>,
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+}
+
+impl<T> Query<T> {
+    /// Returns the data if the query succeeded without any errors
+    pub fn into_data(self) -> Result<T> {
+        if !self.errors.is_empty() {
+            return Err(errors_to_anyhow(&self.errors));
+        }
+        self.data.context("github api returned no data")
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+}
+
+impl GraphQlError {
+    fn is_not_found(&self, path: &[&str]) -> bool {
+        self.kind.as_deref() == Some("NOT_FOUND") && self.path == path
+    }
+}
+
+impl Display for GraphQlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            if i != 0 {
+                f.write_str(".")?;
+            }
+            match segment {
+                serde_json::Value::String(field) => f.write_str(field)?,
+                segment => write!(f, "{segment}")?,
+            }
+        }
+        if !self.path.is_empty() {
+            f.write_str(": ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+fn errors_to_anyhow(errors: &[GraphQlError]) -> anyhow::Error {
+    if errors.is_empty() {
+        return anyhow!("github api response contained neither data nor errors");
+    }
+    let mut msg = String::from("github api returned errors:");
+    for err in errors {
+        format_to!(msg, "\n  {err}");
+    }
+    anyhow!(msg)
+}
+
+/// Escapes `value` for use inside a GraphQL string literal
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The rate limit status that is requested with every query
+#[derive(Deserialize)]
+pub struct RateLimitData {
+    #[serde(rename = "rateLimit")]
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Deserialize)]
+pub struct RateLimit {
+    /// The number of points the query cost
+    pub cost: u64,
+    pub remaining: u64,
+    #[serde(rename = "resetAt")]
+    pub reset_at: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
 pub struct RepoQueryData {
-    pub repository: Repository,
+    pub repository: Option<Repository>,
 }
 
 #[derive(Deserialize)]
 pub struct Repository {
     #[serde(rename = "pullRequest")]
-    pub pull_request: PullRequest,
+    pub pull_request: Option<PullRequestNode>,
 }
 
 #[derive(Deserialize)]
@@ -33,17 +125,171 @@ pub struct Nodes<T> {
 }
 
 #[derive(Deserialize)]
-pub struct PullRequest {
+pub struct SearchResult<T> {
+    #[serde(rename = "issueCount")]
+    pub issue_count: u64,
+    #[serde(flatten)]
+    pub nodes: Nodes<T>,
+}
+
+#[derive(Deserialize)]
+pub struct MergedPr {
+    pub number: u64,
+    #[serde(rename = "mergedAt")]
+    pub merged_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct PullRequestNode {
     pub number: u64,
     pub title: String,
     pub body: String,
     pub labels: Nodes<Label>,
     pub author: Author,
+    #[serde(rename = "closingIssuesReferences")]
+    pub closing_issues_references: Nodes<IssueNode>,
+    pub commits: Nodes<CommitNode>,
+    #[serde(rename = "mergedAt")]
+    pub merged_at: Option<DateTime<Utc>>,
+    #[serde(rename = "mergeCommit")]
+    pub merge_commit: Option<MergeCommit>,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeCommit {
+    pub oid: String,
+}
+
+#[derive(Deserialize)]
+pub struct CommitNode {
+    pub commit: CommitInfo,
+}
+
+#[derive(Deserialize)]
+pub struct CommitInfo {
+    /// The author of the commit followed by all co-authors
+    pub authors: Nodes<GitActor>,
+}
+
+#[derive(Deserialize)]
+pub struct GitActor {
+    pub name: Option<String>,
+    /// The GitHub user the commit was attributed to (if any)
+    pub user: Option<Author>,
+}
+
+#[derive(Deserialize)]
+pub struct IssueNode {
+    pub number: u64,
+    pub repository: RepositoryName,
+}
+
+#[derive(Deserialize)]
+pub struct RepositoryName {
+    #[serde(rename = "nameWithOwner")]
+    pub name_with_owner: String,
+}
+
+impl PullRequestNode {
+    fn into_pull_request(self, repo: &str) -> PullRequest {
+        self.warn_truncated();
+        // issues in other repositories can be closed too but can't be linked like local issues
+        let closed_issues = self
+            .closing_issues_references
+            .nodes
+            .into_iter()
+            .filter(|issue| issue.repository.name_with_owner.eq_ignore_ascii_case(repo))
+            .map(|issue| issue.number)
+            .collect();
+        // the first author is the author of the commit, the others are from `Co-authored-by`
+        // trailers. Co-authors don't need to have a GitHub account
+        let co_authors = self
+            .commits
+            .nodes
+            .into_iter()
+            .flat_map(|commit| commit.commit.authors.nodes.into_iter().skip(1))
+            .filter_map(|actor| match actor.user {
+                Some(user) => Some(user),
+                None => actor.name.as_deref().map(Author::from_name),
+            });
+        let co_authors = forge::dedup_co_authors(&self.author.login, co_authors);
+        PullRequest {
+            number: self.number,
+            title: self.title,
+            body: self.body,
+            labels: self.labels.nodes,
+            author: self.author,
+            closed_issues,
+            co_authors,
+            merged_at: self.merged_at,
+            merge_commit: self.merge_commit.map(|commit| commit.oid),
+            url: self.url,
+        }
+    }
+
+    /// Connections are not paginated, warns if one contains more nodes than were retrieved
+    fn warn_truncated(&self) {
+        let source = format!("PR #{}", self.number);
+        if self.labels.page_info.has_next_page {
+            eprintln!("warning: {source}: has more than 100 labels, only the first 100 are used");
+        }
+        if self.closing_issues_references.page_info.has_next_page {
+            eprintln!(
+                "warning: {source}: closes more than 100 issues, only the first 100 are recorded"
+            );
+        }
+        if self.commits.page_info.has_next_page {
+            eprintln!(
+                "warning: {source}: has more than 100 commits, \
+                co-authors of the remaining commits are not credited"
+            );
+        }
+        if (self.commits.nodes.iter()).any(|commit| commit.commit.authors.page_info.has_next_page) {
+            eprintln!(
+                "warning: {source}: a commit has more than 10 authors, \
+                only the first 10 are credited"
+            );
+        }
+    }
 }
 
+/// The `pull_request` object of a GitHub actions event payload
 #[derive(Deserialize)]
-pub struct Author {
-    pub login: String,
+struct EventPullRequest {
+    number: u64,
+    title: String,
+    body: Option<String>,
+    labels: Vec<Label>,
+    user: Author,
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct PullRequestEvent {
+    pull_request: EventPullRequest,
+}
+
+/// Reads the PR from the event payload of a workflow triggered by a `pull_request` event
+pub fn pr_from_event(path: &Path) -> Result<PullRequest> {
+    let event = read_to_string(path)
+        .with_context(|| format!("failed to read event payload {}", path.display()))?;
+    let event: PullRequestEvent =
+        serde_json::from_str(&event).context("event payload is not a pull_request event")?;
+    let pr = event.pull_request;
+    Ok(PullRequest {
+        number: pr.number,
+        title: pr.title,
+        body: pr.body.unwrap_or_default(),
+        labels: pr.labels,
+        author: pr.user,
+        // not part of the event payload
+        closed_issues: Vec::new(),
+        co_authors: Vec::new(),
+        merged_at: None,
+        merge_commit: None,
+        url: pr.html_url,
+    })
 }
 
 #[derive(Deserialize)]
@@ -54,29 +300,172 @@ pub struct PageInfo {
     pub has_next_page: bool,
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, Deserialize)]
-#[non_exhaustive]
-pub struct Label {
-    pub name: String,
+pub struct GitHub<'a> {
+    pub config: &'a Config,
+    pub transport: &'a dyn Transport,
+}
+
+impl Forge for GitHub<'_> {
+    fn lookup_pr(&self, pr: u64) -> Result<PullRequest> {
+        let pr = lookup_pr(self, pr)?;
+        Ok(pr.into_pull_request(&self.config.repo))
+    }
+
+    fn merged_prs_since(
+        &self,
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PullRequest>> {
+        let mut res = Vec::new();
+        self.merged_prs_between(since, until, &mut res)?;
+        // the boundaries of split search windows may overlap
+        let mut seen = HashSet::new();
+        res.retain(|pr| seen.insert(pr.number));
+        Ok(res
+            .into_iter()
+            .map(|pr| pr.into_pull_request(&self.config.repo))
+            .collect())
+    }
+
+    /// The search can't be sorted by merge time, so the PRs created first are retrieved
+    /// and the one that was merged first is selected. The first merged PR is among them
+    /// unless the author has more than 100 PRs that were open at the same time.
+    fn first_merged_pr(&self, author: &str, until: DateTime<Utc>) -> Result<Option<u64>> {
+        let repo = &self.config.repo;
+        let until = until.to_rfc3339_opts(SecondsFormat::Millis, true);
+        let search = escape(&format!(
+            "repo:{repo} is:pr is:merged author:{author} merged:<={until} sort:created-asc"
+        ));
+        let pr_query = SEARCH_QUERY.replace("DATA", "... on PullRequest { number mergedAt }");
+        let query = formatdoc!(
+            "
+            {{
+              {RATE_LIMIT_QUERY}
+              search(
+                query: \"{search}\",
+                type: ISSUE,
+                first: 100
+            ) {pr_query}
+            }}"
+        );
+        let res: Query<SearchData<SearchResult<MergedPr>>> = self.call_api(&query)?;
+        let prs = res.into_data()?.search.nodes.nodes;
+        Ok(prs.iter().min_by_key(|pr| pr.merged_at).map(|pr| pr.number))
+    }
+}
+
+impl GitHub<'_> {
+    /// The search API returns at most 1000 results (even when paginating).
+    /// Windows that contain more PRs are split in half until all PRs can be retrieved.
+    /// Windows without an `end` are only closed (at the current time) when they need to be split.
+    fn merged_prs_between(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        dst: &mut Vec<PullRequestNode>,
+    ) -> Result<()> {
+        let query = ListPrs {
+            max_fetch: 100,
+            repo: &self.config.repo,
+            filter: Some(PrFilter::Merged(start, end)),
+            ignored_authors: &self.config.ignored_authors,
+            ignored_labels: &self.config.ignored_labels,
+            descending: false,
+            head: None,
+            base: &self.config.main_branch,
+        };
+
+        let SearchResult {
+            issue_count,
+            nodes:
+                Nodes {
+                    nodes: mut res,
+                    mut page_info,
+                },
+        } = query.run(self, None)?;
+
+        if issue_count > MAX_SEARCH_RESULTS {
+            let end = end.unwrap_or_else(Utc::now);
+            if end - start <= Duration::seconds(1) {
+                bail!(
+                    "more than {MAX_SEARCH_RESULTS} PRs were merged between {start} and {end}, \
+                     can not retrieve all of them"
+                )
+            }
+            let mid = start + (end - start) / 2;
+            self.merged_prs_between(start, Some(mid), dst)?;
+            return self.merged_prs_between(mid, Some(end), dst);
+        }
+
+        loop {
+            if !page_info.has_next_page {
+                break;
+            }
+            if let Some(cursor) = page_info.end_cursor {
+                let query = query.run(self, Some(&cursor))?;
+                res.extend(query.nodes.nodes);
+                page_info = query.nodes.page_info;
+            } else {
+                break;
+            }
+        }
+
+        if (res.len() as u64) < issue_count {
+            bail!(
+                "only retrieved {} of the {issue_count} PRs merged since {start}, \
+                 the changelog would be incomplete",
+                res.len()
+            )
+        }
+
+        dst.extend(res);
+        Ok(())
+    }
 }
 
-pub fn lookup_pr(repo: &str, pr: u64) -> Result<PullRequest> {
-    let (owner, repo) = repo.split_once('/').context("invalid repository name")?;
+pub fn lookup_pr(github: &GitHub, pr: u64) -> Result<PullRequestNode> {
+    let (owner, repo) = github
+        .config
+        .repo
+        .split_once('/')
+        .context("invalid repository name")?;
     let request = formatdoc!(
         "
         {{
+         {RATE_LIMIT_QUERY}
          repository(owner: \"{owner}\", name: \"{repo}\") {{
             pullRequest(number: {pr}) {PR_QUERY}    
          }}
         }}"
     );
-    let query: Query<RepoQueryData> = call_api(&request)?;
-    Ok(query.data.repository.pull_request)
+    let query: Query<RepoQueryData> = github.call_api(&request)?;
+    let pull_request = query
+        .data
+        .and_then(|data| data.repository)
+        .and_then(|repository| repository.pull_request);
+    match pull_request {
+        Some(pull_request) => Ok(pull_request),
+        None if query
+            .errors
+            .iter()
+            .any(|err| err.is_not_found(&["repository", "pullRequest"])) =>
+        {
+            bail!("PR #{pr} not found in {owner}/{repo}")
+        }
+        None if query
+            .errors
+            .iter()
+            .any(|err| err.is_not_found(&["repository"])) =>
+        {
+            bail!("repository {owner}/{repo} not found")
+        }
+        None => Err(errors_to_anyhow(&query.errors)),
+    }
 }
 
 pub enum PrFilter {
-    Open,
-    MergedSince(DateTime<Utc>),
+    /// PRs merged after the first and before the second timestamp (if any)
+    Merged(DateTime<Utc>, Option<DateTime<Utc>>),
 }
 
 pub struct ListPrs<'a> {
@@ -91,7 +480,11 @@ pub struct ListPrs<'a> {
 }
 
 impl ListPrs<'_> {
-    pub fn run(&self, cursor: Option<&str>) -> Result<Nodes<PullRequest>> {
+    pub fn run(
+        &self,
+        github: &GitHub,
+        cursor: Option<&str>,
+    ) -> Result<SearchResult<PullRequestNode>> {
         let Self {
             max_fetch,
             repo,
@@ -104,8 +497,8 @@ impl ListPrs<'_> {
         } = *self;
 
         assert!(
-            max_fetch <= 1000,
-            "Can fetch at most 1000 search results at once"
+            max_fetch <= 100,
+            "Can fetch at most 100 search results per page"
         );
 
         let sort = if descending { "desc" } else { "asc" };
@@ -124,15 +517,17 @@ impl ListPrs<'_> {
         }
 
         match filter {
-            Some(PrFilter::MergedSince(merged_since)) => {
-                format_to!(
-                    &mut search,
-                    " merged:{}..{}",
-                    merged_since.to_rfc3339_opts(SecondsFormat::Millis, true),
-                    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
-                );
+            Some(PrFilter::Merged(start, end)) => {
+                let start = start.to_rfc3339_opts(SecondsFormat::Millis, true);
+                match end {
+                    Some(end) => format_to!(
+                        &mut search,
+                        " merged:{start}..{}",
+                        end.to_rfc3339_opts(SecondsFormat::Millis, true)
+                    ),
+                    None => format_to!(&mut search, " merged:>={start}"),
+                }
             }
-            Some(PrFilter::Open) => search.push_str(" is:open"),
             None => (),
         }
 
@@ -143,11 +538,12 @@ impl ListPrs<'_> {
         };
 
         let mut pr_query = format!("... on PullRequest {PR_QUERY}");
-        pr_query = NODES_QUERY.replace("DATA", &pr_query);
+        pr_query = SEARCH_QUERY.replace("DATA", &pr_query);
 
         let query = formatdoc!(
             "
             {{
+              {RATE_LIMIT_QUERY}
               search(
                 query: \"{search}\",
                 type: ISSUE,
@@ -155,8 +551,8 @@ impl ListPrs<'_> {
             ){pr_query}    
             }}",
         );
-        let res: Query<SearchData<_>> = call_api(&query)?;
-        Ok(res.data.search)
+        let res: Query<SearchData<_>> = github.call_api(&query)?;
+        Ok(res.into_data()?.search)
     }
 }
 
@@ -164,6 +560,11 @@ const PR_QUERY: &str = r#"{
   number
   title
   body
+  url
+  mergedAt
+  mergeCommit {
+    oid
+  }
   labels(first: 100) {
     nodes {
       name
@@ -174,12 +575,55 @@ const PR_QUERY: &str = r#"{
     }
   }
   author {
+    __typename
     login
+    ... on User {
+      name
+    }
+  }
+  closingIssuesReferences(first: 100) {
+    nodes {
+      number
+      repository {
+        nameWithOwner
+      }
+    }
+    pageInfo {
+      endCursor
+      hasNextPage
+    }
+  }
+  commits(first: 100) {
+    nodes {
+      commit {
+        authors(first: 10) {
+          nodes {
+            name
+            user {
+              __typename
+              login
+              name
+            }
+          }
+          pageInfo {
+            endCursor
+            hasNextPage
+          }
+        }
+      }
+    }
+    pageInfo {
+      endCursor
+      hasNextPage
+    }
   }
 }
 "#;
 
-const NODES_QUERY: &str = r#"{
+const RATE_LIMIT_QUERY: &str = "rateLimit { cost remaining resetAt }";
+
+const SEARCH_QUERY: &str = r#"{
+  issueCount
   nodes {DATA}
   pageInfo {
     endCursor
@@ -192,26 +636,44 @@ struct GraphQlQuery {
     query: String,
 }
 
-fn call_api<T: for<'de> Deserialize<'de>>(query: &str) -> Result<T> {
-    let token = std::env::var("GITHUB_TOKEN").context("no token set")?;
-    let request = ureq::post("https://api.github.com/graphql")
-        .set("Accept", "application/vnd.github+json")
-        .set("Authorization", &format!("bearer {token}"));
-    let query = GraphQlQuery {
-        query: query.replace('\n', " "),
-    };
-    let query = serde_json::to_string(&query)?;
-    let res = request.send_string(&query).map_err(|err| match err {
-        ureq::Error::Status(status, response) => {
-            anyhow!(
-                "github api call failed (status {status}):\n{}",
-                response.into_string().unwrap_or_default()
-            )
+impl GitHub<'_> {
+    fn call_api<T: for<'de> Deserialize<'de>>(&self, query: &str) -> Result<T> {
+        let token = forge::token(self.config, "GITHUB_TOKEN").context("no token set")?;
+        let api_url = self.config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        let request = ureq::post(&format!("{}/graphql", api_url.trim_end_matches('/')))
+            .set("Accept", "application/vnd.github+json")
+            .set("Authorization", &format!("bearer {token}"));
+        let query = GraphQlQuery {
+            query: query.replace('\n', " "),
+        };
+        let query = serde_json::to_string(&query)?;
+        let res = self
+            .transport
+            .send(self.config, "github", &request, Some(&query))?;
+        if let Ok(Query {
+            data: Some(RateLimitData {
+                rate_limit: Some(rate_limit),
+            }),
+            ..
+        }) = res.json()
+        {
+            self.wait_for_rate_limit(&rate_limit);
         }
-        ureq::Error::Transport(transport) => {
-            anyhow::Error::from(transport).context("sending request to github api failed")
+        res.json()
+    }
+
+    /// Waits for the rate limit to reset if the next query would exceed it
+    /// (assuming it costs as much as the last one) instead of being rejected
+    fn wait_for_rate_limit(&self, rate_limit: &RateLimit) {
+        if rate_limit.remaining >= rate_limit.cost {
+            return;
         }
-    })?;
-    let res = res.into_reader();
-    Ok(serde_json::from_reader(res)?)
+        let wait = (rate_limit.reset_at - Utc::now()).num_seconds().max(0) as u64 + 1;
+        // the next query is rejected and fails instead
+        if wait > self.config.api_limits.max_rate_limit_wait_secs {
+            return;
+        }
+        eprintln!("github api rate limit exhausted, waiting {wait}s until it resets");
+        self.transport.wait(std::time::Duration::from_secs(wait));
+    }
 }
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::forge::{self, Author, Forge, Label, PullRequest};
+use crate::transport::Transport;
+
+const DEFAULT_API_URL: &str = "https://gitlab.com/api/v4";
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    pub iid: u64,
+    pub project_id: u64,
+    pub title: String,
+    pub description: Option<String>,
+    pub labels: Vec<String>,
+    pub author: MergeRequestAuthor,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub merge_commit_sha: Option<String>,
+    /// Only set if the merge request was squashed
+    pub squash_commit_sha: Option<String>,
+    pub web_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequestAuthor {
+    pub username: String,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Commit {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct Issue {
+    pub iid: u64,
+    pub project_id: u64,
+}
+
+impl From<MergeRequest> for PullRequest {
+    fn from(mr: MergeRequest) -> PullRequest {
+        PullRequest {
+            number: mr.iid,
+            title: mr.title,
+            body: mr.description.unwrap_or_default(),
+            labels: mr.labels.into_iter().map(|name| Label { name }).collect(),
+            author: Author {
+                kind: is_bot_user(&mr.author.username).then(|| "Bot".to_owned()),
+                login: mr.author.username,
+                name: mr.author.name,
+            },
+            closed_issues: Vec::new(),
+            co_authors: Vec::new(),
+            merged_at: mr.merged_at,
+            // fast-forward merges don't create a merge commit
+            merge_commit: mr.merge_commit_sha.or(mr.squash_commit_sha),
+            url: mr.web_url,
+        }
+    }
+}
+
+/// The merge request API doesn't report the kind of account, but the bot users of
+/// project and group access tokens are named `project_<ID>_bot_<SUFFIX>` (or `group_…`)
+fn is_bot_user(username: &str) -> bool {
+    let mut parts = username.splitn(3, '_');
+    matches!(parts.next(), Some("project" | "group"))
+        && parts
+            .next()
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|c| c.is_ascii_digit()))
+        && parts.next().is_some_and(|rest| rest.starts_with("bot"))
+}
+
+pub struct GitLab<'a> {
+    pub config: &'a Config,
+    pub transport: &'a dyn Transport,
+}
+
+impl GitLab<'_> {
+    fn project_url(&self) -> String {
+        let api_url = self.config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        let project = self.config.repo.replace('/', "%2F");
+        format!("{}/projects/{project}", api_url.trim_end_matches('/'))
+    }
+
+    /// Performs a `GET` request and returns the deserialized response
+    /// together with the next page (for paginated endpoints)
+    fn call_api<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<(T, Option<String>)> {
+        let mut request = ureq::get(&format!("{}/{endpoint}", self.project_url()));
+        if let Some(token) = forge::token(self.config, "GITLAB_TOKEN") {
+            request = request.set("PRIVATE-TOKEN", &token);
+        }
+        for (param, value) in query {
+            request = request.query(param, value);
+        }
+        let res = self.transport.send(self.config, "gitlab", &request, None)?;
+        let next_page = res
+            .header("x-next-page")
+            .filter(|page| !page.is_empty())
+            .map(str::to_owned);
+        Ok((res.json()?, next_page))
+    }
+
+    /// Retrieves all pages of a paginated endpoint
+    fn call_api_paginated<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut page = "1".to_owned();
+        let mut res = Vec::new();
+        loop {
+            let mut page_query = query.to_vec();
+            page_query.extend([("per_page", "100"), ("page", &page)]);
+            let (items, next_page) = self.call_api::<Vec<T>>(endpoint, &page_query)?;
+            res.extend(items);
+            match next_page {
+                Some(next_page) => page = next_page,
+                None => return Ok(res),
+            }
+        }
+    }
+
+    /// Converts a merge request and retrieves the issues it closes and the authors of its commits
+    fn pull_request(&self, mr: MergeRequest) -> Result<PullRequest> {
+        let issues: Vec<Issue> =
+            self.call_api_paginated(&format!("merge_requests/{}/closes_issues", mr.iid), &[])?;
+        let commits: Vec<Commit> =
+            self.call_api_paginated(&format!("merge_requests/{}/commits", mr.iid), &[])?;
+        let project_id = mr.project_id;
+        let mut pr = PullRequest::from(mr);
+        pr.closed_issues = issues
+            .into_iter()
+            .filter(|issue| issue.project_id == project_id)
+            .map(|issue| issue.iid)
+            .collect();
+        let co_authors = commits
+            .iter()
+            .flat_map(|commit| forge::co_authors(&commit.message))
+            .map(Author::from_name);
+        pr.co_authors = forge::dedup_co_authors(&pr.author.login, co_authors);
+        Ok(pr)
+    }
+}
+
+impl Forge for GitLab<'_> {
+    fn lookup_pr(&self, pr: u64) -> Result<PullRequest> {
+        let (mr, _) = self.call_api::<MergeRequest>(&format!("merge_requests/{pr}"), &[])?;
+        self.pull_request(mr)
+    }
+
+    fn merged_prs_since(
+        &self,
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PullRequest>> {
+        let updated_after = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut res: Vec<MergeRequest> = self.call_api_paginated(
+            "merge_requests",
+            &[
+                ("state", "merged"),
+                ("target_branch", &self.config.main_branch),
+                ("updated_after", &updated_after),
+            ],
+        )?;
+        // a merge request may have been updated after it was merged
+        res.retain(|mr| {
+            mr.merged_at.is_some_and(|merged_at| {
+                merged_at >= since && until.is_none_or(|until| merged_at <= until)
+            })
+        });
+        res.sort_by_key(|mr| mr.merged_at);
+        res.into_iter().map(|mr| self.pull_request(mr)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_bot_user;
+
+    #[test]
+    fn bot_users() {
+        assert!(is_bot_user("project_278964_bot_4d7c1f0a2b3e"));
+        assert!(is_bot_user("group_9970_bot_e0a2"));
+        assert!(is_bot_user("project_278964_bot"));
+        assert!(!is_bot_user("project_bot"));
+        assert!(!is_bot_user("project_x_bot"));
+        assert!(!is_bot_user("alice"));
+    }
+}
@@ -1,125 +1,195 @@
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::fs::read_to_string;
+use std::path::Path;
 
-use anyhow::{Context, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 use clap::Parser;
+use indexmap::IndexMap;
 use tera::Tera;
 use xshell::{cmd, Shell};
 
-use crate::config::Config;
-use crate::github_api::PullRequest;
-use crate::state::{Change, ReleaseState};
-use crate::tera_functions::{make_pr_list_md, make_pr_md_link, make_pr_url, upper_first_filter};
+use crate::config::{Config, ForgeKind};
+use crate::entries::{changelog_entries, check_group, ignore_reason, Entries, IgnoreReason};
+use crate::forge::{Forge, PullRequest};
+use crate::state::{Change, Changelog, MergedPr, Release, ReleaseState, StateDocument};
+use crate::tera_functions::{
+    make_author_link, make_commit_md_link, make_commit_url, make_issue_list_md, make_issue_md_link,
+    make_issue_url, make_pr_list_md, make_pr_md_link, make_pr_url, upper_first_filter, Links,
+};
+use crate::transport::Transport;
 
 #[macro_use]
 mod util;
 mod cli;
 mod config;
+mod conventional_commits;
+mod entries;
+mod forge;
+mod gen;
+mod git;
+mod gitea_api;
 mod github_api;
+mod gitlab_api;
+mod parser;
 mod state;
 mod tera_functions;
+mod transport;
 
-impl github_api::PullRequest {
-    pub fn is_ignored(&self, config: &Config) -> bool {
-        for label in &self.labels.nodes {
-            if config.ignored_labels.contains(&label.name) {
-                return true;
-            }
-        }
+impl PullRequest {
+    pub fn ignore_reason(&self, config: &Config) -> Option<IgnoreReason> {
+        ignore_reason(
+            config,
+            &self.title,
+            &self.body,
+            &[&self.author.login],
+            self.author.is_bot(),
+            &self.labels,
+        )
+    }
 
-        if config.ignored_authors.contains(&self.author.login) {
-            return true;
+    pub fn changelog_entries(&self, config: &Config, dst: &mut ReleaseState) -> Result<()> {
+        let entries = self.entries(config);
+        entries.report(config, &format!("PR #{}", self.number))?;
+        for change in entries.changes {
+            let meta = dst.insert_pr_change(change, self.number);
+            meta.issue.0.extend(self.closed_issues.iter().copied());
+            meta.author
+                .0
+                .extend(self.accounts().map(|author| author.login.clone()));
+            meta.author_name.0.extend(
+                self.co_authors
+                    .iter()
+                    .filter(|author| author.kind.is_none())
+                    .map(|author| author.login.clone()),
+            );
         }
-
-        for prefix in config.ignored_title_prefix.iter() {
-            if self.title.trim().starts_with(prefix) {
-                return true;
-            }
-        }
-
-        false
+        Ok(())
     }
 
-    pub fn changelog_entries<'a>(&'a self, config: &'a Config, dst: &mut ReleaseState) {
-        if self.is_ignored(config) {
-            return;
-        }
+    pub fn entries(&self, config: &Config) -> Entries {
+        changelog_entries(config, &self.title, &self.body, &self.labels)
+    }
 
-        let mut main_change = None;
+    /// `None` if the PR wasn't merged (yet)
+    pub fn merged(&self) -> Option<MergedPr> {
+        Some(MergedPr {
+            pr: self.number,
+            merged_at: self.merged_at?,
+            merge_commit: self.merge_commit.clone(),
+            url: self.url.clone(),
+        })
+    }
+}
 
-        let mut generate_main_change = true;
+impl git::Commit {
+    pub fn ignore_reason(&self, config: &Config) -> Option<IgnoreReason> {
+        let is_bot = forge::is_bot_name(&self.author);
+        // `ignored_authors` can list the name, the email or the login of the author
+        let mut author = vec![&*self.author, &*self.email];
+        author.extend(self.login());
+        ignore_reason(config, &self.subject, &self.body, &author, is_bot, &[])
+    }
 
-        for line in self.body.lines() {
-            if let Some((_, mut rem)) = line.trim().split_once("changelog") {
-                let mut group = None;
-                if rem.starts_with('[') {
-                    if let Some((group_, rem_)) = rem[1..].split_once(']') {
-                        group = match config.label_groups.get(group_) {
-                            Some(group_) => Some(group_.to_owned()),
-                            None => Some(group_.to_owned()),
-                        };
-                        rem = rem_;
-                    }
-                }
-                if !rem.starts_with(':') {
-                    continue;
-                }
+    pub fn changelog_entries(&self, config: &Config, dst: &mut ReleaseState) -> Result<()> {
+        let entries = changelog_entries(config, &self.subject, &self.body, &[]);
+        entries.report(config, &format!("commit {}", self.hash))?;
+        for change in entries.changes {
+            let meta = dst.insert_commit_change(change, self.hash.clone());
+            match self.login() {
+                Some(login) => meta.author.0.insert(login.to_owned()),
+                None => meta.author_name.0.insert(self.author.clone()),
+            };
+            meta.author_name
+                .0
+                .extend(forge::co_authors(&self.body).map(str::to_owned));
+        }
+        Ok(())
+    }
+}
 
-                if let Some(group) = group {
-                    dst.insert_pr_change(
-                        Change {
-                            message: rem.trim_start().to_owned(),
-                            group,
-                        },
-                        self.number,
-                    );
-                } else {
-                    main_change = Some(rem.trim_start().to_owned())
-                }
+impl ReleaseState {
+    pub fn add_pr_changes(&mut self, pr: &PullRequest, config: &Config) -> Result<()> {
+        if let Some(reason) = pr.ignore_reason(config) {
+            println!("skipping PR #{}: {reason}", pr.number);
+            return Ok(());
+        }
+        let mut generated = ReleaseState::default();
+        pr.changelog_entries(config, &mut generated)?;
+        self.insert_pr_changes(pr.number, generated.changes);
+        // changes of the PR that were edited by hand are kept but record the merge as well
+        if let Some(merged) = pr.merged() {
+            self.insert_merged_pr(&merged);
+        }
+        for author in pr.accounts() {
+            self.author_names
+                .insert(author.login.clone(), author.display_name().to_owned());
+        }
+        Ok(())
+    }
 
-                generate_main_change = false;
-            }
+    /// Updates the changes previously generated from `pr` instead of adding new changes
+    pub fn sync_pr_changes(&mut self, pr: &PullRequest, config: &Config) -> Result<()> {
+        let mut generated = ReleaseState::default();
+        generated.add_pr_changes(pr, config)?;
+        self.author_names.extend(generated.author_names);
+        self.replace_pr_changes(pr.number, generated.changes);
+        if let Some(merged) = pr.merged() {
+            self.insert_merged_pr(&merged);
         }
+        Ok(())
+    }
 
-        if main_change.is_none() {
-            main_change = Some(self.title.trim().to_owned());
+    pub fn add_commit_changes(&mut self, commit: &git::Commit, config: &Config) -> Result<()> {
+        if let Some(reason) = commit.ignore_reason(config) {
+            println!("skipping commit {}: {reason}", commit.hash);
+            return Ok(());
         }
+        commit.changelog_entries(config, self)
+    }
 
-        if let Some(main_change) = main_change {
-            for label in &self.labels.nodes {
-                if let Some(group) = config.label_groups.get(&label.name) {
-                    generate_main_change = false;
-                    dst.insert_pr_change(
-                        Change {
-                            message: main_change.clone(),
-                            group: group.to_owned(),
-                        },
-                        self.number,
-                    );
-                }
+    /// Records the authors of `prs` whose first merged PR is part of this release
+    pub fn add_new_contributors(
+        &mut self,
+        forge: &dyn Forge,
+        prs: &[PullRequest],
+        config: &Config,
+    ) -> Result<()> {
+        let release_prs: HashSet<u64> = self
+            .changes
+            .values()
+            .flat_map(|meta| meta.pr.0.iter().copied())
+            .chain(prs.iter().map(|pr| pr.number))
+            .collect();
+        // the first PR of a new contributor was merged at the latest with their last PR
+        let mut last_merged: IndexMap<&str, DateTime<Utc>> = IndexMap::new();
+        for pr in prs {
+            let author = &pr.author.login;
+            if pr.ignore_reason(config).is_some() || self.new_contributors.contains_key(author) {
+                continue;
             }
-            if generate_main_change {
-                if let Some(group) = &config.default_group {
-                    dst.insert_pr_change(
-                        Change {
-                            message: main_change,
-                            group: group.to_owned(),
-                        },
-                        self.number,
-                    );
+            if let Some(merged_at) = pr.merged_at {
+                let last = last_merged.entry(author).or_insert(merged_at);
+                *last = merged_at.max(*last);
+            }
+        }
+        for (author, until) in last_merged {
+            if let Some(first_pr) = forge.first_merged_pr(author, until)? {
+                if release_prs.contains(&first_pr) {
+                    self.new_contributors.insert(author.to_owned(), first_pr);
                 }
             }
         }
-    }
-}
-
-impl ReleaseState {
-    pub fn add_pr_changes(&mut self, pr: &PullRequest, config: &Config) {
-        pr.changelog_entries(config, self);
-        self.authors.insert(pr.author.login.clone());
+        Ok(())
     }
 
-    pub fn render(&self, config: &Config, version: Option<&str>) -> Result<String> {
+    pub fn render(
+        &self,
+        config: &Config,
+        version: Option<&str>,
+        date: Option<NaiveDate>,
+    ) -> Result<String> {
         let mut tera = Tera::default();
         tera.add_raw_template("template", &config.template)?;
         let mut ctx = self.to_tera_ctx();
@@ -137,25 +207,79 @@ impl ReleaseState {
 
         let mut ctx = tera::Context::from_serialize(ctx)?;
         ctx.insert("version", &version);
+        ctx.insert("date", &date);
         ctx.insert("repo", &config.repo);
+        let links = Links::new(config)?;
+        ctx.insert("web_url", &links.web_url);
         tera.register_filter("upper_first", upper_first_filter);
-        tera.register_function("pr_url", make_pr_url(config.repo.clone()));
-        tera.register_function("pr_md_link", make_pr_md_link(config.repo.clone()));
-        tera.register_function("pr_list_md", make_pr_list_md(config.repo.clone()));
+        tera.register_function("pr_url", make_pr_url(links.clone()));
+        tera.register_function("pr_md_link", make_pr_md_link(links.clone()));
+        tera.register_function("pr_list_md", make_pr_list_md(links.clone()));
+        tera.register_function("commit_url", make_commit_url(links.clone()));
+        tera.register_function("commit_md_link", make_commit_md_link(links.clone()));
+        tera.register_function("issue_url", make_issue_url(links.clone()));
+        tera.register_function("issue_md_link", make_issue_md_link(links.clone()));
+        tera.register_function("issue_list_md", make_issue_list_md(links.clone()));
+        tera.register_function(
+            "author_link",
+            make_author_link(links, self.author_names.clone()),
+        );
         let res = tera.render("template", &ctx)?;
         Ok(res)
     }
 }
 
+impl Release {
+    pub fn render(&self, config: &Config) -> Result<String> {
+        self.state
+            .render(config, Some(&self.version), Some(self.date))
+    }
+}
+
 impl cli::Render {
-    pub fn run(&self, config: &Config, state: &ReleaseState) -> Result<bool> {
-        println!("{}", state.render(config, self.version.as_deref())?);
+    pub fn run(&self, config: &Config, state: &Changelog) -> Result<bool> {
+        if self.all {
+            let mut releases = Vec::new();
+            if !state.unreleased.changes.is_empty() {
+                releases.push(state.unreleased.render(config, None, None)?);
+            }
+            for release in &state.releases {
+                releases.push(release.render(config)?);
+            }
+            let releases: Vec<_> = releases.iter().map(|release| release.trim()).collect();
+            println!("{}", releases.join("\n\n"));
+        } else {
+            let version = self.version.as_deref();
+            let res = match version.and_then(|version| state.find_release(version)) {
+                Some(release) => release.render(config)?,
+                None => state.unreleased.render(config, version, None)?,
+            };
+            println!("{res}");
+        }
         Ok(false)
     }
 }
 
+impl cli::AddCommits {
+    pub fn run(&self, config: &Config, state: &mut ReleaseState) -> Result<bool> {
+        let commits = git::commits_since(&self.since_ref).context("failed to retrieve commits")?;
+        for commit in commits {
+            state.add_commit_changes(&commit, config)?;
+        }
+        Ok(true)
+    }
+}
+
+impl cli::Archive {
+    pub fn run(&self, state: &mut Changelog) -> Result<bool> {
+        let date = self.date.unwrap_or_else(|| Utc::now().naive_utc().date());
+        state.archive(&self.version, date)?;
+        Ok(true)
+    }
+}
+
 impl cli::AddPr {
-    pub fn get_prs(&self, config: &Config) -> Result<Vec<PullRequest>> {
+    pub fn get_prs(&self, forge: &dyn Forge) -> Result<Vec<PullRequest>> {
         let timestamp = self
             .since_ref
             .as_ref()
@@ -168,71 +292,319 @@ impl cli::AddPr {
             .or_else(|| self.since_timestamp.map(Ok));
         if let Some(timestamp) = timestamp {
             let timestamp = timestamp.context("failed to obtain timestamp for git rev")? + 10;
-            let timestamp = NaiveDateTime::from_timestamp(timestamp as i64, 0);
-            let timestamp = DateTime::from_utc(timestamp, Utc);
-            let query = github_api::ListPrs {
-                max_fetch: 100,
-                repo: &config.repo,
-                filter: Some(github_api::PrFilter::MergedSince(timestamp)),
-                ignored_authors: &config.ignored_authors,
-                ignored_labels: &config.ignored_labels,
-                descending: false,
-                head: None,
-                base: &config.main_branch,
+            let to_date_time = |timestamp: u64| {
+                Utc.timestamp_opt(timestamp as i64, 0)
+                    .single()
+                    .context("invalid timestamp")
             };
+            let until = self.until_timestamp.map(to_date_time).transpose()?;
+            forge.merged_prs_since(to_date_time(timestamp)?, until)
+        } else {
+            self.prs.iter().map(|&pr| forge.lookup_pr(pr)).collect()
+        }
+    }
+    pub fn run(
+        &self,
+        config: &Config,
+        transport: &dyn Transport,
+        state: &mut ReleaseState,
+    ) -> Result<bool> {
+        let forge = forge::from_config(config, transport)?;
+        let prs = self.get_prs(&*forge).context("failed to retrieve PRs")?;
+        for pr in &prs {
+            if self.sync {
+                state.sync_pr_changes(pr, config)?;
+            } else {
+                state.add_pr_changes(pr, config)?;
+            }
+        }
+        if config.detect_new_contributors {
+            if matches!(config.forge, ForgeKind::GitHub) {
+                state
+                    .add_new_contributors(&*forge, &prs, config)
+                    .context("failed to detect new contributors")?;
+            } else {
+                eprintln!(
+                    "warning: new contributors are not detected, \
+                     `detect_new_contributors` is only supported for GitHub"
+                );
+            }
+        }
+        Ok(true)
+    }
+}
 
-            let github_api::Nodes {
-                nodes: mut res,
-                mut page_info,
-            } = query.run(None)?;
+impl cli::CheckPr {
+    pub fn run(&self, config: &Config, transport: &dyn Transport) -> Result<bool> {
+        let pr = match self.pr {
+            Some(pr) => forge::from_config(config, transport)?.lookup_pr(pr)?,
+            None => {
+                let event_path = std::env::var_os("GITHUB_EVENT_PATH")
+                    .context("no PR specified and GITHUB_EVENT_PATH is not set")?;
+                github_api::pr_from_event(event_path.as_ref())?
+            }
+        };
+        if let Some(reason) = pr.ignore_reason(config) {
+            println!(
+                "PR #{} will not be added to the changelog: {reason}",
+                pr.number
+            );
+            return Ok(false);
+        }
 
-            loop {
-                if !page_info.has_next_page {
-                    break;
-                }
-                if let Some(cursor) = page_info.end_cursor {
-                    let query = query.run(Some(&cursor))?;
-                    res.extend(query.nodes);
-                    page_info = query.page_info;
-                } else {
-                    break;
+        let entries = pr.entries(config);
+        let mut problems = entries.problems;
+        problems.extend(entries.unknown_groups.iter().map(ToString::to_string));
+        if entries.changes.is_empty() {
+            problems.push("no changelog entry would be generated".to_owned());
+        }
+        println!("PR #{} adds the following changelog entries:", pr.number);
+        for change in &entries.changes {
+            let message = change.message.replace('\n', "\n    ");
+            println!("  [{}] {message}", change.group);
+            let reported = entries
+                .unknown_groups
+                .iter()
+                .any(|unknown| unknown.name == change.group);
+            if !config.is_known_group(&change.group) && !reported {
+                problems.push(format!("unknown group `{}`", change.group));
+            }
+        }
+
+        if !problems.is_empty() {
+            let mut msg = format!("PR #{} has invalid changelog metadata:", pr.number);
+            for problem in problems {
+                format_to!(msg, "\n  {problem}");
+            }
+            bail!(msg)
+        }
+        Ok(false)
+    }
+}
+
+impl cli::Merge {
+    pub fn run(&self, config: &Config, state: &mut ReleaseState) -> Result<bool> {
+        if self.suggest {
+            let similar = state.similar_changes();
+            if similar.is_empty() {
+                println!("no similar changes found");
+            }
+            for indices in similar {
+                let group = &state.changes.get_index(indices[0]).unwrap().0.group;
+                println!("possible duplicates in {group}:");
+                for &i in &indices {
+                    let (change, meta) = state.changes.get_index(i).unwrap();
+                    print!("  {}: {}", i + 1, change.message);
+                    for pr in &meta.pr.0 {
+                        print!(" #{pr}");
+                    }
+                    println!();
                 }
+                let args: Vec<_> = indices.iter().map(|i| (i + 1).to_string()).collect();
+                println!("  merge with `crabby_changes merge {}`", args.join(" "));
+            }
+            return Ok(false);
+        }
+
+        if let Some(group) = &self.group {
+            check_group(config, group)?;
+        }
+        let indices = state.select_changes(&self.changes)?;
+        let merged = state.merge_changes(&indices, self.message.clone(), self.group.clone())?;
+        println!("merged changes into [{}] {}", merged.group, merged.message);
+        Ok(true)
+    }
+}
+
+impl cli::Add {
+    pub fn run(
+        &self,
+        config: &Config,
+        state: &mut ReleaseState,
+        document: Option<&mut StateDocument>,
+    ) -> Result<bool> {
+        check_group(config, &self.group)?;
+        let change = Change {
+            message: self.message.trim().to_owned(),
+            group: self.group.clone(),
+        };
+        if change.message.is_empty() {
+            bail!("the message of a change can't be empty")
+        }
+        let msg = format!("[{}] {}", change.group, change.message);
+        let added = state.add_change(change.clone(), &self.prs);
+        if let Some(document) = document {
+            let meta = &state.changes[&change];
+            if added {
+                document.push_change(&change, meta)?;
+            } else if !self.prs.is_empty() {
+                document.update_prs(&change, meta)?;
             }
-            Ok(res)
+        }
+        if added {
+            println!("added {msg}");
         } else {
-            self.prs
-                .iter()
-                .map(|&pr| github_api::lookup_pr(&config.repo, pr))
-                .collect()
+            println!("{msg} already exists");
+        }
+        Ok(true)
+    }
+}
+
+impl cli::Remove {
+    pub fn run(
+        &self,
+        state: &mut ReleaseState,
+        mut document: Option<&mut StateDocument>,
+    ) -> Result<bool> {
+        let indices = state.select_changes(&self.changes)?;
+        let removed_before = state.removed.len();
+        for change in state.remove_changes(&indices) {
+            if let Some(document) = document.as_deref_mut() {
+                document.remove_change(&change);
+            }
+            println!("removed [{}] {}", change.group, change.message);
         }
+        if let Some(document) = document {
+            for removed in &state.removed[removed_before..] {
+                document.push_removed(removed)?;
+            }
+        }
+        Ok(true)
     }
-    pub fn run(&self, config: &Config, state: &mut ReleaseState) -> Result<bool> {
-        for pr in self.get_prs(config).context("failed to retrieve PRs")? {
-            state.add_pr_changes(&pr, config);
+}
+
+impl cli::Move {
+    pub fn run(
+        &self,
+        config: &Config,
+        state: &mut ReleaseState,
+        document: Option<&mut StateDocument>,
+    ) -> Result<bool> {
+        check_group(config, &self.group)?;
+        let indices = state.select_changes(&self.changes)?;
+        let len_before = state.changes.len();
+        let moved = state.move_changes(&indices, &self.group);
+        if let Some(document) = document {
+            if state.changes.len() == len_before {
+                for change in &moved {
+                    document.move_change(change, &self.group);
+                }
+            } else {
+                // some changes were merged into existing changes of the group
+                document.rewrite();
+            }
+        }
+        for change in moved {
+            println!(
+                "moved {} from [{}] to [{}]",
+                change.message, change.group, self.group
+            );
         }
         Ok(true)
     }
 }
 
+impl cli::Release {
+    pub fn run(&self, config: &Config, state: &mut Changelog, state_file: &Path) -> Result<bool> {
+        let sh = Shell::new()?;
+
+        state.archive(&self.version, Utc::now().naive_utc().date())?;
+        let release = state.releases[0].render(config)?;
+        let mut changelog = read_to_string(&config.changelog_file).unwrap_or_default();
+        gen::update_changelog(&mut changelog, &release);
+
+        let mut version_files = Vec::new();
+        if !config.version_files.is_empty() {
+            let prev_version = match &self.prev_version {
+                Some(prev_version) => prev_version.clone(),
+                None => gen::find_latest_version(&sh, &config.tag_prefix)?
+                    .context("no previous release tag found, specify --prev-version")?,
+            };
+            version_files =
+                gen::replace_versions(&prev_version, &self.version, &config.version_files)?;
+        }
+
+        // only write files once all of them were updated successfully
+        let state = state.to_toml()?;
+        std::fs::write(&config.changelog_file, changelog)?;
+        for (file, content) in version_files {
+            std::fs::write(file, content)?;
+        }
+        // the archived changes are part of the release commit
+        std::fs::write(state_file, state)?;
+
+        if !self.no_commit {
+            let tag = format!("{}{}", config.tag_prefix, self.version);
+            gen::create_release_commit(
+                &sh,
+                &tag,
+                &self.version,
+                &config.changelog_file,
+                state_file,
+                &config.version_files,
+            )?;
+        }
+
+        // the statefile was written already
+        Ok(false)
+    }
+}
+
 impl cli::CliArgs {
     fn run(self) -> Result<()> {
-        let state = read_to_string(&self.state).unwrap_or_default();
-        let mut state = if state.is_empty() {
+        let src = read_to_string(&self.state).unwrap_or_default();
+        let mut state = if src.is_empty() {
             println!("statefile not found, generating a new release");
-            ReleaseState::default()
+            Changelog::default()
         } else {
-            toml::from_str(&state)?
+            Changelog::from_toml(&src)?
         };
+        // commands that only edit single changes keep the formatting and comments of the statefile
+        let in_place = matches!(
+            self.command,
+            cli::Commands::Add(_) | cli::Commands::Remove(_) | cli::Commands::Move(_)
+        );
+        let mut document = in_place.then(|| StateDocument::parse(&src)).flatten();
+        if let Some(document) = &mut document {
+            document.mark_edited(&state);
+        }
         let config = read_to_string(&self.config).context("config not found")?;
         let config = toml::from_str(&config)?;
 
+        let transport: Box<dyn Transport> = match (self.record_fixtures, self.replay_fixtures) {
+            (Some(dir), _) => Box::new(transport::Record {
+                dir,
+                next: Cell::new(0),
+            }),
+            (_, Some(dir)) => Box::new(transport::Replay {
+                dir,
+                next: Cell::new(0),
+            }),
+            (None, None) => Box::new(transport::Http),
+        };
+
         let state_modified = match self.command {
             cli::Commands::Render(cmd) => cmd.run(&config, &state)?,
-            cli::Commands::AddPr(cmd) => cmd.run(&config, &mut state)?,
+            cli::Commands::AddPr(cmd) => cmd.run(&config, &*transport, &mut state.unreleased)?,
+            cli::Commands::AddCommits(cmd) => cmd.run(&config, &mut state.unreleased)?,
+            cli::Commands::Archive(cmd) => cmd.run(&mut state)?,
+            cli::Commands::Release(cmd) => cmd.run(&config, &mut state, &self.state)?,
+            cli::Commands::CheckPr(cmd) => cmd.run(&config, &*transport)?,
+            cli::Commands::Merge(cmd) => cmd.run(&config, &mut state.unreleased)?,
+            cli::Commands::Add(cmd) => {
+                cmd.run(&config, &mut state.unreleased, document.as_mut())?
+            }
+            cli::Commands::Remove(cmd) => cmd.run(&mut state.unreleased, document.as_mut())?,
+            cli::Commands::Move(cmd) => {
+                cmd.run(&config, &mut state.unreleased, document.as_mut())?
+            }
         };
 
         if state_modified {
-            let state = toml::to_string_pretty(&state)?;
+            let state = match document.and_then(StateDocument::finish) {
+                Some(state) => state,
+                None => state.to_toml()?,
+            };
             std::fs::write(&self.state, state)?;
         }
 
@@ -242,6 +614,7 @@ impl cli::CliArgs {
 
 fn main() {
     if let Err(err) = cli::CliArgs::parse().run() {
-        eprintln!("error: {err:?}")
+        eprintln!("error: {err:?}");
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,431 @@
+//! Parser for the changelog directives in PR descriptions and commit messages.
+//!
+//! A directive is a line of the form `changelog[group]: message` where the group is optional.
+//! The `changelog` keyword is matched case-insensitively and must be the first word of the line,
+//! only a markdown list marker (`-`, `*`, `+` or `1.`) may precede it.
+//! Longer entries can be written in a fenced block with the `changelog` info string:
+//! every directive inside the block starts a new entry that spans all following lines.
+//!
+//! Lines inside other code blocks, HTML comments and quotes (`>`) are never parsed as directives.
+//! HTML comments are also removed from changelog blocks.
+//!
+//! Lines that start with the keyword but are not valid directives are reported as malformed,
+//! unless they look like a sentence (a capitalized keyword without a `:` later in the line).
+
+/// A `changelog[group]: message` line or an entry of a changelog block
+pub struct Directive {
+    pub group: Option<String>,
+    pub message: String,
+    /// The line the directive was parsed from, used for diagnostics
+    pub line: String,
+}
+
+#[derive(Default)]
+pub struct Parsed {
+    pub directives: Vec<Directive>,
+    /// Malformed directives that were ignored
+    pub problems: Vec<String>,
+}
+
+const KEYWORD: &str = "changelog";
+
+pub fn parse(body: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut in_comment = false;
+    let mut lines = body.lines();
+    while let Some(line) = lines.next() {
+        let line = strip_comments(line, &mut in_comment);
+        let line = line.trim();
+        if let Some((fence, info)) = fence_start(line) {
+            if info.eq_ignore_ascii_case(KEYWORD) {
+                parse_block(line, fence, &mut lines, &mut parsed);
+            } else {
+                // skip the content of unrelated code blocks
+                for line in lines.by_ref() {
+                    if is_fence_end(line, fence) {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        if line.starts_with('>') {
+            continue;
+        }
+        match parse_directive(line) {
+            Ok(Some((group, message))) => parsed.directives.push(Directive {
+                group: group.map(str::to_owned),
+                message: message.trim().to_owned(),
+                line: line.to_owned(),
+            }),
+            Ok(None) => (),
+            Err(problem) => parsed.problems.push(problem),
+        }
+    }
+    parsed
+}
+
+/// Removes (possibly multi-line) HTML comments from a line
+fn strip_comments(mut line: &str, in_comment: &mut bool) -> String {
+    let mut res = String::new();
+    loop {
+        if *in_comment {
+            let Some(end) = line.find("-->") else {
+                break;
+            };
+            line = &line[end + 3..];
+            *in_comment = false;
+        } else {
+            let Some(start) = line.find("<!--") else {
+                res.push_str(line);
+                break;
+            };
+            res.push_str(&line[..start]);
+            line = &line[start + 4..];
+            *in_comment = true;
+        }
+    }
+    res
+}
+
+/// Returns the fence and the info string if the line opens a code block
+fn fence_start(line: &str) -> Option<(&str, &str)> {
+    let fence_char = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = line.len() - line.trim_start_matches(fence_char).len();
+    let (fence, info) = line.split_at(len);
+    if len < 3 || (fence_char == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((fence, info.trim()))
+}
+
+fn is_fence_end(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    let fence_char = fence.as_bytes()[0] as char;
+    line.starts_with(fence) && line.trim_start_matches(fence_char).is_empty()
+}
+
+/// Removes a markdown list marker (`-`, `*`, `+`, `1.` or `1)`) from the start of a line
+fn strip_list_marker(line: &str) -> &str {
+    let rest = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            if rest.len() == line.len() {
+                return line;
+            }
+            match rest.strip_prefix(['.', ')']) {
+                Some(rest) => rest,
+                None => return line,
+            }
+        }
+    };
+    if rest.starts_with(char::is_whitespace) {
+        rest.trim_start()
+    } else {
+        line
+    }
+}
+
+/// Parses a `changelog[group]: message` line, returns the group and the (untrimmed) message
+fn parse_directive(line: &str) -> Result<Option<(Option<&str>, &str)>, String> {
+    let rest = strip_list_marker(line.trim());
+    let Some(keyword) = rest.get(..KEYWORD.len()) else {
+        return Ok(None);
+    };
+    if !keyword.eq_ignore_ascii_case(KEYWORD) {
+        return Ok(None);
+    }
+    let mut rest = &rest[KEYWORD.len()..];
+    let mut group = None;
+    if let Some(group_) = rest.strip_prefix('[') {
+        let Some((group_, rest_)) = group_.split_once(']') else {
+            return Err(format!("missing `]` in `{line}`"));
+        };
+        let group_ = group_.trim();
+        if group_.is_empty() {
+            return Err(format!("empty group in `{line}`"));
+        }
+        group = Some(group_);
+        rest = rest_;
+    } else if !rest.starts_with(|c: char| c == ':' || c.is_whitespace()) {
+        // a longer word like `changelogs`
+        return Ok(None);
+    }
+    match rest.trim_start().strip_prefix(':') {
+        Some(message) => Ok(Some((group, message))),
+        // a sentence like "Changelog updates are listed below", directives are usually
+        // written in lowercase or contain a `:` later in the line (`changelog Fixes: foo`)
+        None if group.is_none() && keyword != KEYWORD && !rest.contains(':') => Ok(None),
+        None => Err(format!("missing `:` in `{line}`")),
+    }
+}
+
+/// Parses the lines of a changelog block up to the closing fence.
+/// Every directive starts a new entry, text before the first directive forms a main entry.
+fn parse_block<'a>(
+    opening_line: &str,
+    fence: &str,
+    lines: &mut impl Iterator<Item = &'a str>,
+    parsed: &mut Parsed,
+) {
+    let mut closed = false;
+    let mut in_comment = false;
+    let mut block = Vec::new();
+    for line in lines.by_ref() {
+        if is_fence_end(line, fence) {
+            closed = true;
+            break;
+        }
+        let stripped = strip_comments(line, &mut in_comment);
+        // lines that only contain a comment are dropped instead of leaving a blank line
+        if stripped.trim().is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        block.push(stripped);
+    }
+
+    // the header (group, text and line) of the current entry, `None` for the leading main entry
+    let mut header: Option<(Option<&str>, &str, &str)> = None;
+    let mut message: Vec<&str> = Vec::new();
+    let mut finish = |header: Option<(Option<&str>, &str, &str)>, message: &mut Vec<&str>| {
+        let mut text = dedent(message);
+        message.clear();
+        let Some((group, first_line, line)) = header else {
+            // an empty main entry just means the block starts with a directive
+            if !text.is_empty() {
+                parsed.directives.push(Directive {
+                    group: None,
+                    message: text,
+                    line: opening_line.to_owned(),
+                });
+            }
+            return;
+        };
+        let first_line = first_line.trim();
+        if !first_line.is_empty() {
+            text = if text.is_empty() {
+                first_line.to_owned()
+            } else {
+                format!("{first_line}\n{text}")
+            };
+        }
+        parsed.directives.push(Directive {
+            group: group.map(str::to_owned),
+            message: text,
+            line: line.to_owned(),
+        });
+    };
+    let mut problems = Vec::new();
+    for line in &block {
+        match parse_directive(line) {
+            Ok(Some((group, first_line))) => {
+                finish(header, &mut message);
+                header = Some((group, first_line, line.trim()));
+            }
+            Ok(None) => message.push(line),
+            Err(problem) => problems.push(problem),
+        }
+    }
+    finish(header, &mut message);
+    parsed.problems.extend(problems);
+    if !closed {
+        parsed
+            .problems
+            .push(format!("unclosed {opening_line} block"));
+    }
+}
+
+/// Removes the common indentation and leading/trailing blank lines of a multi-line message
+fn dedent(lines: &[&str]) -> String {
+    let is_blank = |line: &&str| line.trim().is_empty();
+    let start = lines.iter().position(|line| !is_blank(line));
+    let end = lines.iter().rposition(|line| !is_blank(line));
+    let (Some(start), Some(end)) = (start, end) else {
+        return String::new();
+    };
+    let lines = &lines[start..=end];
+    let indent = lines
+        .iter()
+        .filter(|line| !is_blank(line))
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directives(body: &str) -> Vec<(Option<String>, String)> {
+        let parsed = parse(body);
+        assert_eq!(parsed.problems, Vec::<String>::new());
+        parsed
+            .directives
+            .into_iter()
+            .map(|directive| (directive.group, directive.message))
+            .collect()
+    }
+
+    fn main(message: &str) -> (Option<String>, String) {
+        (None, message.to_owned())
+    }
+
+    fn grouped(group: &str, message: &str) -> (Option<String>, String) {
+        (Some(group.to_owned()), message.to_owned())
+    }
+
+    #[test]
+    fn directive() {
+        assert_eq!(directives("changelog: Add foo"), [main("Add foo")]);
+        assert_eq!(
+            directives("  changelog[Features]:   Add foo  "),
+            [grouped("Features", "Add foo")]
+        );
+        assert_eq!(directives("changelog : Add foo"), [main("Add foo")]);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(directives("Changelog: Add foo"), [main("Add foo")]);
+        assert_eq!(
+            directives("CHANGELOG[Fixes]: Fix foo"),
+            [grouped("Fixes", "Fix foo")]
+        );
+    }
+
+    #[test]
+    fn list_markers() {
+        let body =
+            "- changelog: a\n* changelog: b\n+ changelog: c\n1. changelog: d\n2) changelog: e";
+        assert_eq!(
+            directives(body),
+            [main("a"), main("b"), main("c"), main("d"), main("e")]
+        );
+        assert_eq!(directives("-changelog: a\n1changelog: b"), []);
+    }
+
+    #[test]
+    fn only_at_start_of_line() {
+        assert_eq!(directives("This updates the changelog: see below"), []);
+        assert_eq!(directives("See changelog[Features]: foo"), []);
+        assert_eq!(directives("Changelog updates are listed below"), []);
+        assert_eq!(directives("CHANGELOG was updated"), []);
+        assert_eq!(directives("changelogs: foo"), []);
+    }
+
+    #[test]
+    fn malformed() {
+        let parsed = parse("changelog[Features: foo\nchangelog[Features] foo\nchangelog[]: foo");
+        assert!(parsed.directives.is_empty());
+        assert_eq!(
+            parsed.problems,
+            [
+                "missing `]` in `changelog[Features: foo`",
+                "missing `:` in `changelog[Features] foo`",
+                "empty group in `changelog[]: foo`",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_colon() {
+        let parsed = parse(
+            "changelog Fix crash when opening a directory\n\
+             Changelog Fixes: Fix crash\n\
+             changelog [Features]: Add foo",
+        );
+        assert!(parsed.directives.is_empty());
+        assert_eq!(
+            parsed.problems,
+            [
+                "missing `:` in `changelog Fix crash when opening a directory`",
+                "missing `:` in `Changelog Fixes: Fix crash`",
+                "missing `:` in `changelog [Features]: Add foo`",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_code_blocks() {
+        let body = "```\nchangelog: a\n```\n~~~~md\nchangelog: b\n~~~\n~~~~\nchangelog: c";
+        assert_eq!(directives(body), [main("c")]);
+    }
+
+    #[test]
+    fn ignores_html_comments() {
+        let body = "<!-- changelog: a -->\n<!--\nchangelog: b\n-->\n<!-- x --> changelog: c";
+        assert_eq!(directives(body), [main("c")]);
+    }
+
+    #[test]
+    fn ignores_quotes() {
+        let body = "> changelog: a\n>changelog: b\n> > changelog: c";
+        assert_eq!(directives(body), []);
+    }
+
+    #[test]
+    fn block() {
+        let body = "\
+```changelog
+Add `:sort` command
+
+changelog[Breaking changes]: The `sort` keybinding was removed, use
+- `:sort` or
+- `:rsort` instead
+  changelog[Fixes]:
+    Fix sorting
+      by length
+
+changelog: Add `:rsort` command
+```
+changelog: after";
+        assert_eq!(
+            directives(body),
+            [
+                main("Add `:sort` command"),
+                grouped(
+                    "Breaking changes",
+                    "The `sort` keybinding was removed, use\n- `:sort` or\n- `:rsort` instead"
+                ),
+                grouped("Fixes", "Fix sorting\n  by length"),
+                main("Add `:rsort` command"),
+                main("after"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_ignores_html_comments() {
+        let body = "\
+```changelog
+<!-- describe the change for users -->
+Add `:sort` command <!-- and `:rsort` -->
+
+<!--
+changelog[Fixes]: Fix sorting
+-->
+changelog[Breaking changes]: The `sort` keybinding was removed
+```";
+        assert_eq!(
+            directives(body),
+            [
+                main("Add `:sort` command"),
+                grouped("Breaking changes", "The `sort` keybinding was removed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_block() {
+        let parsed = parse("````Changelog\nAdd foo\n```");
+        assert_eq!(parsed.directives.len(), 1);
+        assert_eq!(parsed.directives[0].message, "Add foo\n```");
+        assert_eq!(parsed.problems, ["unclosed ````Changelog block"]);
+    }
+}
@@ -1,38 +1,684 @@
-use std::mem::transmute;
+use std::mem::{take, transmute};
 
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use indexmap::map::Entry;
 use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
+use toml_edit::{Document, Item, Table, Value};
 
 use crate::state::one_or_many::OneOrMany;
+use crate::util::{edit_distance, stable_hash};
 
+mod document;
 mod map_to_list;
 mod one_or_many;
 
+pub use document::StateDocument;
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct Change {
     pub message: String,
     pub group: String,
 }
 
+impl Change {
+    /// Hash of the message and group, used to detect entries that were edited by hand.
+    /// Short enough not to clutter the statefile, collisions only matter between changes of the same PR.
+    pub fn content_hash(&self) -> String {
+        let hash = stable_hash(format!("{}\0{}", self.group, self.message).as_bytes());
+        format!("{:08x}", hash >> 32)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 pub struct ChangeMeta<const PRETTY: bool> {
+    #[serde(default, skip_serializing_if = "OneOrMany::is_omitted")]
     pub pr: OneOrMany<u64, PRETTY>,
+    #[serde(default, skip_serializing_if = "OneOrMany::is_omitted")]
+    pub commit: OneOrMany<String, PRETTY>,
+    /// Issues closed by the PRs
+    #[serde(default, skip_serializing_if = "OneOrMany::is_omitted")]
+    pub issue: OneOrMany<u64, PRETTY>,
+    /// Logins of the authors and co-authors of the PRs/commits
+    #[serde(default, skip_serializing_if = "OneOrMany::is_omitted")]
+    pub author: OneOrMany<String, PRETTY>,
+    /// Authors without an account on the forge that are only known by their name
+    /// (co-authors from `Co-authored-by` trailers and commit authors)
+    #[serde(default, skip_serializing_if = "OneOrMany::is_omitted")]
+    pub author_name: OneOrMany<String, PRETTY>,
+    /// `content_hash` of the change when it was generated, `None` for changes added by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    /// Set once a generated change was edited by hand and never reset,
+    /// even if the edit is reverted later
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub edited: bool,
+    /// When and by which commit the PRs were merged (one entry per merged PR)
+    #[serde(default, skip_serializing_if = "is_omitted_vec::<_, PRETTY>")]
+    pub merged: Vec<MergedPr>,
+}
+
+/// When and by which commit a PR was merged
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MergedPr {
+    pub pr: u64,
+    pub merged_at: DateTime<Utc>,
+    /// `None` for fast-forward merges
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_commit: Option<String>,
+    pub url: String,
+}
+
+/// A generated change that was removed by hand, so that adding its PR again doesn't bring it back
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Removed {
+    pub pr: u64,
+    /// The `generated` hash of the removed change
+    pub generated: String,
+}
+
+/// Where a change came from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Provenance {
+    /// Generated from a PR or commit and not edited since
+    Generated,
+    /// Generated from a PR or commit but edited by hand afterwards
+    Edited,
+    /// Added by hand
+    Manual,
+}
+
+impl<const PRETTY: bool> ChangeMeta<PRETTY> {
+    /// Metadata for a change that was just generated
+    fn generated(change: &Change) -> ChangeMeta<PRETTY> {
+        ChangeMeta {
+            generated: Some(change.content_hash()),
+            ..ChangeMeta::default()
+        }
+    }
+
+    pub fn provenance(&self, change: &Change) -> Provenance {
+        match &self.generated {
+            None => Provenance::Manual,
+            Some(hash) if self.edited || *hash != change.content_hash() => Provenance::Edited,
+            Some(_) => Provenance::Generated,
+        }
+    }
+
+    /// Only changes that were generated and never edited may be modified by the tool
+    pub fn is_generated(&self, change: &Change) -> bool {
+        self.provenance(change) == Provenance::Generated
+    }
+
+    /// Adds the metadata of a duplicate change
+    pub fn merge(&mut self, other: ChangeMeta<PRETTY>) {
+        self.pr.0.extend(other.pr.0);
+        self.commit.0.extend(other.commit.0);
+        self.issue.0.extend(other.issue.0);
+        self.author.0.extend(other.author.0);
+        self.author_name.0.extend(other.author_name.0);
+        for merged in other.merged {
+            insert_merged_pr(&mut self.merged, merged);
+        }
+        // a change only counts as generated if all merged changes were generated,
+        // so that edits are never lost
+        if other.generated.is_none() {
+            self.generated = None;
+        }
+        self.edited |= other.edited;
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ReleaseStateImpl<const PRETTY: bool> {
+    /// Authors that are credited in addition to the authors of the changes
+    #[serde(default, skip_serializing_if = "is_omitted_set::<_, PRETTY>")]
     pub authors: IndexSet<String>,
-    #[serde(with = "map_to_list")]
+    #[serde(default, with = "map_to_list")]
     pub changes: IndexMap<Change, ChangeMeta<PRETTY>>,
+    /// Display names of the authors that have an account on the forge (by login)
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub author_names: IndexMap<String, String>,
+    /// Authors whose first merged PR is part of this release, mapped to that PR
+    #[serde(default, skip_serializing_if = "is_omitted_map::<_, _, PRETTY>")]
+    pub new_contributors: IndexMap<String, u64>,
+    /// Generated changes that were removed by hand
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<Removed>,
 }
 
 pub type ReleaseState = ReleaseStateImpl<true>;
 
+/// Empty sets are omitted from the statefile but kept for templates
+fn is_omitted_set<T, const PRETTY: bool>(set: &IndexSet<T>) -> bool {
+    PRETTY && set.is_empty()
+}
+
+fn is_omitted_map<K, V, const PRETTY: bool>(map: &IndexMap<K, V>) -> bool {
+    PRETTY && map.is_empty()
+}
+
+fn is_omitted_vec<T, const PRETTY: bool>(list: &[T]) -> bool {
+    PRETTY && list.is_empty()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Release {
+    pub version: String,
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub state: ReleaseState,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Changelog {
+    #[serde(default)]
+    pub unreleased: ReleaseState,
+    /// Past releases, the most recent release comes first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub releases: Vec<Release>,
+}
+
+impl Changelog {
+    pub fn from_toml(src: &str) -> Result<Changelog> {
+        let state: toml::Value = toml::from_str(src)?;
+        // statefiles used to only contain the unreleased changes
+        let legacy = state.get("changes").is_some() || state.get("authors").is_some();
+        let mut changelog = if legacy {
+            Changelog {
+                unreleased: state.try_into()?,
+                releases: Vec::new(),
+            }
+        } else {
+            state.try_into()?
+        };
+        changelog.unreleased.mark_edited();
+        for release in &mut changelog.releases {
+            release.state.mark_edited();
+        }
+        Ok(changelog)
+    }
+
+    /// Serializes the changelog in the format of the statefile. The `merged` records of a change
+    /// are written inline, one `[[changes.merged]]` table per PR would bury the changes.
+    pub fn to_toml(&self) -> Result<String> {
+        let mut doc: Document = toml::to_string_pretty(self)?.parse()?;
+        inline_merged(doc.as_table_mut());
+        Ok(doc.to_string())
+    }
+
+    pub fn find_release(&self, version: &str) -> Option<&Release> {
+        self.releases
+            .iter()
+            .find(|release| release.version == version)
+    }
+
+    /// Moves all unreleased changes into a new release
+    pub fn archive(&mut self, version: &str, date: NaiveDate) -> Result<()> {
+        if self.find_release(version).is_some() {
+            bail!("version {version} was already released")
+        }
+        let release = Release {
+            version: version.to_owned(),
+            date,
+            state: std::mem::take(&mut self.unreleased),
+        };
+        self.releases.insert(0, release);
+        Ok(())
+    }
+}
+
 impl ReleaseState {
-    pub fn insert_pr_change(&mut self, change: Change, pr: u64) {
-        self.changes.entry(change).or_default().pr.0.insert(pr);
+    pub fn insert_pr_change(&mut self, change: Change, pr: u64) -> &mut ChangeMeta<true> {
+        let meta = self
+            .changes
+            .entry(change)
+            .or_insert_with_key(ChangeMeta::generated);
+        meta.pr.0.insert(pr);
+        meta
+    }
+    pub fn insert_commit_change(
+        &mut self,
+        change: Change,
+        commit: String,
+    ) -> &mut ChangeMeta<true> {
+        let meta = self
+            .changes
+            .entry(change)
+            .or_insert_with_key(ChangeMeta::generated);
+        meta.commit.0.insert(commit);
+        meta
+    }
+
+    /// Records when `merged.pr` was merged on every change of the PR,
+    /// replacing a previous record
+    pub fn insert_merged_pr(&mut self, merged: &MergedPr) {
+        for meta in self.changes.values_mut() {
+            if meta.pr.0.contains(&merged.pr) {
+                insert_merged_pr(&mut meta.merged, merged.clone());
+            }
+        }
     }
+
+    /// Remembers which generated changes were edited by hand (see [`ChangeMeta::edited`])
+    pub fn mark_edited(&mut self) {
+        for (change, meta) in &mut self.changes {
+            if meta.provenance(change) == Provenance::Edited {
+                meta.edited = true;
+            }
+        }
+    }
+    /// Adds the changes generated from `pr`
+    pub fn insert_pr_changes(&mut self, pr: u64, mut new: IndexMap<Change, ChangeMeta<true>>) {
+        self.drop_superseded(pr, &mut new);
+        for (change, meta) in new {
+            insert_merged(&mut self.changes, change, meta);
+        }
+    }
+
+    /// Replaces the changes generated from `pr` by the `new` changes generated from it.
+    /// Changes are updated in place if their group didn't change.
+    /// Changes that were edited by hand (or are shared with other PRs/commits) are kept as is.
+    pub fn replace_pr_changes(&mut self, pr: u64, mut new: IndexMap<Change, ChangeMeta<true>>) {
+        self.drop_superseded(pr, &mut new);
+        let owned = |change: &Change, meta: &ChangeMeta<true>| {
+            meta.is_generated(change) && meta.commit.0.is_empty() && meta.pr.0.iter().eq([&pr])
+        };
+        let mut changes = IndexMap::with_capacity(self.changes.len());
+        for (change, meta) in take(&mut self.changes) {
+            if !owned(&change, &meta) {
+                insert_merged(&mut changes, change, meta);
+                continue;
+            }
+            let replacement = match new.shift_remove(&change) {
+                Some(meta) => Some((change, meta)),
+                None => new
+                    .keys()
+                    .position(|new_change| new_change.group == change.group)
+                    .and_then(|i| new.shift_remove_index(i)),
+            };
+            // no replacement means that the changelog entry was removed from the PR
+            if let Some((change, meta)) = replacement {
+                insert_merged(&mut changes, change, meta);
+            }
+        }
+        for (change, meta) in new {
+            insert_merged(&mut changes, change, meta);
+        }
+        self.changes = changes;
+    }
+
+    /// Drops the `new` changes of `pr` that are regenerated versions of changes of `pr`
+    /// that were edited (or added) by hand, adding them would bring back the text that was
+    /// edited away. Every such change supersedes the new change with its original text,
+    /// or else the first new change in its group (like [`Self::replace_pr_changes`]).
+    ///
+    /// Statefiles written by older versions don't record which changes were generated.
+    /// Such changes are adopted as generated if they match a `new` change exactly,
+    /// otherwise they can't be told apart from changes added by hand.
+    ///
+    /// New changes that were removed by hand (see `removed`) are dropped as well.
+    fn drop_superseded(&mut self, pr: u64, new: &mut IndexMap<Change, ChangeMeta<true>>) {
+        new.retain(|change, _| {
+            let hash = change.content_hash();
+            let removed = self
+                .removed
+                .iter()
+                .any(|removed| removed.pr == pr && removed.generated == hash);
+            if removed {
+                println!(
+                    "PR #{pr}: not adding [{}] {}, which was removed by hand",
+                    change.group, change.message
+                );
+            }
+            !removed
+        });
+        for (change, meta) in &mut self.changes {
+            if !meta.pr.0.contains(&pr) || meta.is_generated(change) {
+                continue;
+            }
+            if meta.generated.is_none() && meta.commit.0.is_empty() && new.contains_key(change) {
+                meta.generated = Some(change.content_hash());
+                continue;
+            }
+            let original = new
+                .keys()
+                .position(|new| meta.generated.as_ref() == Some(&new.content_hash()));
+            let Some(i) = original.or_else(|| new.keys().position(|new| new.group == change.group))
+            else {
+                continue;
+            };
+            let (superseded, _) = new.shift_remove_index(i).unwrap();
+            let reason = if meta.generated.is_some() {
+                "was edited by hand"
+            } else {
+                "has no `generated` hash (it was added by hand or by an older version)"
+            };
+            println!(
+                "PR #{pr}: not adding [{}] {} in place of [{}] {}, which {reason}",
+                superseded.group, superseded.message, change.group, change.message
+            );
+        }
+    }
+
+    /// Returns the sorted indices of the changes selected on the command line,
+    /// either by their position (starting at 1) or with `#<PR>` for all changes of a PR
+    pub fn select_changes(&self, selectors: &[String]) -> Result<Vec<usize>> {
+        let mut indices = Vec::new();
+        for selector in selectors {
+            if let Some(pr) = selector.strip_prefix('#') {
+                let pr: u64 = pr
+                    .parse()
+                    .with_context(|| format!("invalid PR number `{pr}`"))?;
+                let len = indices.len();
+                indices.extend(
+                    self.changes
+                        .values()
+                        .enumerate()
+                        .filter(|(_, meta)| meta.pr.0.contains(&pr))
+                        .map(|(i, _)| i),
+                );
+                if indices.len() == len {
+                    bail!("no unreleased change for PR #{pr}")
+                }
+            } else {
+                let i: usize = selector.parse().ok().filter(|&i| i != 0).with_context(|| {
+                    format!("invalid change `{selector}`, expected a position or `#<PR>`")
+                })?;
+                if i > self.changes.len() {
+                    bail!(
+                        "there is no change {i} (there are {} unreleased changes)",
+                        self.changes.len()
+                    )
+                }
+                indices.push(i - 1);
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        Ok(indices)
+    }
+
+    /// Merges the changes at the (sorted) `indices` into a single change (with the metadata
+    /// of all changes) at the position of the first change.
+    /// Keeps the message of the first change unless a new `message` is given.
+    /// Changes in different groups are only merged if the `group` of the merged change is given.
+    pub fn merge_changes(
+        &mut self,
+        indices: &[usize],
+        message: Option<String>,
+        group: Option<String>,
+    ) -> Result<Change> {
+        if indices.len() < 2 {
+            bail!("at least two changes are required for merging")
+        }
+        let groups: IndexSet<&str> = indices
+            .iter()
+            .map(|&i| &*self.changes.get_index(i).unwrap().0.group)
+            .collect();
+        if groups.len() > 1 && group.is_none() {
+            let groups: Vec<_> = groups.into_iter().collect();
+            bail!(
+                "the changes are in different groups ({}), \
+                 pass `--group` to choose the group of the merged change",
+                groups.join(", ")
+            )
+        }
+        let mut selected = indices.iter().map(|&i| self.changes[i].clone());
+        let mut merged = self.changes.get_index(indices[0]).unwrap().0.clone();
+        let mut meta = selected.next().unwrap();
+        for other in selected {
+            meta.merge(other);
+        }
+        if let Some(message) = message {
+            merged.message = message;
+        }
+        if let Some(group) = group {
+            merged.group = group;
+        }
+        // merging is an edit, regenerating any of the merged changes would create a duplicate
+        meta.edited |= meta.generated.is_some();
+        let mut merged_change = Some((merged.clone(), meta));
+        for (i, (change, meta)) in take(&mut self.changes).into_iter().enumerate() {
+            if i == indices[0] {
+                let (change, meta) = merged_change.take().unwrap();
+                insert_merged(&mut self.changes, change, meta);
+            } else if indices.binary_search(&i).is_err() {
+                insert_merged(&mut self.changes, change, meta);
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Adds a change by hand, returns `false` if the change already existed
+    pub fn add_change(&mut self, change: Change, prs: &[u64]) -> bool {
+        let exists = self.changes.contains_key(&change);
+        let meta = self.changes.entry(change).or_default();
+        meta.pr.0.extend(prs);
+        !exists
+    }
+
+    /// Removes the changes at the (sorted) `indices`.
+    /// Generated changes are recorded in `removed` so that they are not added again.
+    pub fn remove_changes(&mut self, indices: &[usize]) -> Vec<Change> {
+        let mut removed = Vec::new();
+        for (i, (change, meta)) in take(&mut self.changes).into_iter().enumerate() {
+            if indices.binary_search(&i).is_ok() {
+                if let Some(generated) = &meta.generated {
+                    self.removed.extend(meta.pr.0.iter().map(|&pr| Removed {
+                        pr,
+                        generated: generated.clone(),
+                    }));
+                }
+                removed.push(change);
+            } else {
+                self.changes.insert(change, meta);
+            }
+        }
+        removed
+    }
+
+    /// Moves the changes at the (sorted) `indices` to `group` without changing their position,
+    /// returns the moved changes (with their previous group)
+    pub fn move_changes(&mut self, indices: &[usize], group: &str) -> Vec<Change> {
+        let mut moved = Vec::new();
+        for (i, (mut change, meta)) in take(&mut self.changes).into_iter().enumerate() {
+            if indices.binary_search(&i).is_ok() {
+                moved.push(change.clone());
+                change.group = group.to_owned();
+            }
+            // the moved change may already exist in the new group
+            insert_merged(&mut self.changes, change, meta);
+        }
+        moved
+    }
+
+    /// Returns sets of (indices of) changes in the same group with similar messages
+    pub fn similar_changes(&self) -> Vec<Vec<usize>> {
+        let normalize =
+            |change: &Change| change.message.trim().trim_end_matches('.').to_lowercase();
+        let is_similar = |a: &str, b: &str| {
+            // allow one edit for every four characters
+            edit_distance(a, b) * 4 <= a.chars().count().max(b.chars().count())
+        };
+        let messages: Vec<String> = self.changes.keys().map(normalize).collect();
+        let mut clustered = vec![false; messages.len()];
+        let mut res = Vec::new();
+        for (i, (change, _)) in self.changes.iter().enumerate() {
+            if clustered[i] {
+                continue;
+            }
+            let cluster: Vec<usize> = (i..messages.len())
+                .filter(|&j| {
+                    !clustered[j]
+                        && self.changes.get_index(j).unwrap().0.group == change.group
+                        && is_similar(&messages[i], &messages[j])
+                })
+                .collect();
+            if cluster.len() > 1 {
+                for &j in &cluster {
+                    clustered[j] = true;
+                }
+                res.push(cluster);
+            }
+        }
+        res
+    }
+
+    /// The release-wide `authors` contain the authors of all changes
     pub fn to_tera_ctx(&self) -> ReleaseStateImpl<false> {
-        unsafe { transmute(self.clone()) }
+        let mut state = self.clone();
+        for meta in state.changes.values() {
+            state.authors.extend(meta.author.0.iter().cloned());
+            state.authors.extend(meta.author_name.0.iter().cloned());
+        }
+        unsafe { transmute(state) }
+    }
+}
+
+/// Turns the `merged` arrays of tables into arrays of inline tables with one table per line
+fn inline_merged(table: &mut Table) {
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::ArrayOfTables(_) if key == "merged" => {
+                key.fmt();
+                let Item::ArrayOfTables(merged) = take(item) else {
+                    unreachable!()
+                };
+                let mut merged = merged.into_array();
+                for merged_pr in merged.iter_mut() {
+                    merged_pr.decor_mut().set_prefix("\n    ");
+                }
+                merged.set_trailing_comma(true);
+                merged.set_trailing("\n");
+                *item = Item::Value(Value::Array(merged));
+            }
+            Item::Table(table) => inline_merged(table),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(inline_merged),
+            _ => (),
+        }
+    }
+}
+
+fn insert_merged_pr(dst: &mut Vec<MergedPr>, merged: MergedPr) {
+    dst.retain(|other| other.pr != merged.pr);
+    dst.push(merged);
+}
+
+fn insert_merged(
+    changes: &mut IndexMap<Change, ChangeMeta<true>>,
+    change: Change,
+    meta: ChangeMeta<true>,
+) {
+    match changes.entry(change) {
+        Entry::Occupied(entry) => entry.into_mut().merge(meta),
+        Entry::Vacant(entry) => {
+            entry.insert(meta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, Changelog, MergedPr, ReleaseState};
+
+    fn change(group: &str, message: &str) -> Change {
+        Change {
+            message: message.to_owned(),
+            group: group.to_owned(),
+        }
+    }
+
+    fn generated(changes: &[Change], pr: u64) -> ReleaseState {
+        let mut state = ReleaseState::default();
+        for change in changes {
+            state.insert_pr_change(change.clone(), pr);
+        }
+        state
+    }
+
+    #[test]
+    fn drop_only_superseded() {
+        let mut state = generated(&[change("Fixes", "Fix crash")], 1);
+        let (edited, meta) = state.changes.pop().unwrap();
+        let edited = Change {
+            message: "Fix crash when closing the last buffer".to_owned(),
+            ..edited
+        };
+        state.changes.insert(edited.clone(), meta);
+        state.mark_edited();
+
+        let messages = |state: &ReleaseState| -> Vec<String> {
+            state
+                .changes
+                .keys()
+                .map(|change| change.message.clone())
+                .collect()
+        };
+
+        let new = generated(
+            &[
+                change("Fixes", "Fix crash"),
+                change("Fixes", "Fix panic in the picker"),
+            ],
+            1,
+        );
+        state.insert_pr_changes(1, new.changes);
+        assert_eq!(
+            messages(&state),
+            [
+                "Fix crash when closing the last buffer",
+                "Fix panic in the picker"
+            ]
+        );
+
+        // after the PR description changed the first new change in the group is superseded
+        let new = generated(
+            &[
+                change("Fixes", "Fix a crash"),
+                change("Fixes", "Fix a leak"),
+            ],
+            1,
+        );
+        state.insert_pr_changes(1, new.changes);
+        assert_eq!(
+            messages(&state),
+            [
+                "Fix crash when closing the last buffer",
+                "Fix panic in the picker",
+                "Fix a leak"
+            ]
+        );
+    }
+
+    #[test]
+    fn inline_merged_prs() {
+        let mut changelog = Changelog {
+            unreleased: generated(&[change("Fixes", "Fix crash")], 1),
+            releases: Vec::new(),
+        };
+        changelog.unreleased.insert_merged_pr(&MergedPr {
+            pr: 1,
+            merged_at: "2022-10-12T12:00:00Z".parse().unwrap(),
+            merge_commit: None,
+            url: "https://github.com/helix-editor/helix/pull/1".to_owned(),
+        });
+        let toml = changelog.to_toml().unwrap();
+        assert_eq!(
+            toml,
+            "\
+[[unreleased.changes]]
+message = 'Fix crash'
+group = 'Fixes'
+pr = 1
+generated = '9c5cb75e'
+merged = [
+    { pr = 1, merged_at = '2022-10-12T12:00:00Z', url = 'https://github.com/helix-editor/helix/pull/1' },
+]
+"
+        );
+        let parsed = Changelog::from_toml(&toml).unwrap();
+        assert!(parsed.unreleased.changes == changelog.unreleased.changes);
     }
 }
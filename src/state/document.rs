@@ -0,0 +1,252 @@
+use anyhow::Result;
+use indexmap::IndexMap;
+use toml_edit::{ArrayOfTables, Document, Item, RawString, Table};
+
+use crate::state::{Change, ChangeMeta, Changelog, ReleaseState, Removed};
+
+/// The statefile as it was written, so that changes can be edited without touching
+/// the formatting and comments of the rest of the file.
+///
+/// Only files that already contain an array of `[[unreleased.changes]]` tables
+/// (or `[[changes]]` for legacy statefiles) can be edited in place, all other files
+/// are written in the canonical format instead.
+pub struct StateDocument {
+    doc: Document,
+    legacy: bool,
+    /// Whether all edits could be applied to the document
+    in_sync: bool,
+}
+
+impl StateDocument {
+    pub fn parse(src: &str) -> Option<StateDocument> {
+        let doc: Document = src.parse().ok()?;
+        let legacy = doc.get("changes").is_some() || doc.get("authors").is_some();
+        let mut doc = StateDocument {
+            doc,
+            legacy,
+            in_sync: true,
+        };
+        doc.changes()?;
+        Some(doc)
+    }
+
+    /// The statefile with all edits applied,
+    /// `None` if an edit could not be applied and the file has to be rewritten
+    pub fn finish(self) -> Option<String> {
+        self.in_sync.then(|| self.doc.to_string())
+    }
+
+    fn release(&mut self) -> Option<&mut Table> {
+        if self.legacy {
+            Some(self.doc.as_table_mut())
+        } else {
+            self.doc.get_mut("unreleased")?.as_table_mut()
+        }
+    }
+
+    fn changes(&mut self) -> Option<&mut ArrayOfTables> {
+        self.release()?.get_mut("changes")?.as_array_of_tables_mut()
+    }
+
+    /// Returns the positions of the tables of `change`
+    fn find(&mut self, change: &Change) -> Vec<usize> {
+        match self.changes() {
+            Some(changes) => positions(changes, change),
+            None => Vec::new(),
+        }
+    }
+
+    /// Writes the `edited` flags that were set while loading the statefile
+    /// (see [`ReleaseState::mark_edited`]), otherwise they would be lost with the next
+    /// in-place edit and reverting the edit would make the change count as generated again
+    pub fn mark_edited(&mut self, changelog: &Changelog) {
+        let mut in_sync = mark_edited(self.changes(), &changelog.unreleased.changes);
+        for (i, release) in changelog.releases.iter().enumerate() {
+            let changes = (self.doc.get_mut("releases"))
+                .and_then(Item::as_array_of_tables_mut)
+                .and_then(|releases| releases.get_mut(i))
+                .and_then(|release| release.get_mut("changes"))
+                .and_then(Item::as_array_of_tables_mut);
+            in_sync &= mark_edited(changes, &release.state.changes);
+        }
+        self.in_sync &= in_sync;
+    }
+
+    /// Appends a change to the unreleased changes
+    pub fn push_change(&mut self, change: &Change, meta: &ChangeMeta<true>) -> Result<()> {
+        let table = canonical_table(change.clone(), meta.clone())?;
+        match self.changes() {
+            Some(changes) => changes.push(table),
+            None => self.in_sync = false,
+        }
+        Ok(())
+    }
+
+    /// Replaces the PRs of an existing change with the PRs in `meta`
+    pub fn update_prs(&mut self, change: &Change, meta: &ChangeMeta<true>) -> Result<()> {
+        let table = canonical_table(change.clone(), meta.clone())?;
+        let pr = table.get("pr").cloned();
+        let positions = self.find(change);
+        let (Some(pr), [i]) = (pr, &*positions) else {
+            self.in_sync = false;
+            return Ok(());
+        };
+        let changes = self.changes().unwrap();
+        let table = changes.get_mut(*i).unwrap();
+        match table.get_mut("pr") {
+            Some(old) => replace_value(old, pr),
+            None => {
+                table.insert("pr", pr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Falls back to writing the whole statefile in the canonical format
+    pub fn rewrite(&mut self) {
+        self.in_sync = false;
+    }
+
+    /// Removes a change from the unreleased changes
+    pub fn remove_change(&mut self, change: &Change) {
+        let positions = self.find(change);
+        let [i] = &*positions else {
+            self.in_sync = false;
+            return;
+        };
+        let changes = self.changes().unwrap();
+        let prefix = changes.get(*i).unwrap().decor().prefix().cloned();
+        changes.remove(*i);
+        // the next change takes the place of the removed change, unless there is a comment above it
+        let blank = |prefix: Option<&RawString>| {
+            prefix
+                .and_then(RawString::as_str)
+                .is_none_or(|prefix| prefix.trim().is_empty())
+        };
+        if let (Some(prefix), Some(next)) = (prefix, changes.get_mut(*i)) {
+            if blank(Some(&prefix)) && blank(next.decor().prefix()) {
+                next.decor_mut().set_prefix(prefix);
+            }
+        }
+    }
+
+    /// Moves a change to `group` without changing its position
+    pub fn move_change(&mut self, change: &Change, group: &str) {
+        let positions = self.find(change);
+        let [i] = &*positions else {
+            self.in_sync = false;
+            return;
+        };
+        let table = self.changes().unwrap().get_mut(*i).unwrap();
+        replace_value(table.get_mut("group").unwrap(), toml_edit::value(group));
+    }
+
+    /// Records that a generated change was removed
+    pub fn push_removed(&mut self, removed: &Removed) -> Result<()> {
+        let mut state = ReleaseState::default();
+        state.removed.push(removed.clone());
+        let mut table = release_table(state)?;
+        let Some(Item::ArrayOfTables(new)) = table.remove("removed") else {
+            unreachable!("removed changes are always written")
+        };
+        let Some(release) = self.release() else {
+            self.in_sync = false;
+            return Ok(());
+        };
+        let removed = release
+            .entry("removed")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()));
+        match removed.as_array_of_tables_mut() {
+            Some(removed) => removed.extend(new.iter().map(without_position)),
+            None => self.in_sync = false,
+        }
+        Ok(())
+    }
+}
+
+/// Returns the positions of the tables of `change` in `changes`
+fn positions(changes: &ArrayOfTables, change: &Change) -> Vec<usize> {
+    fn field<'a>(table: &'a Table, key: &str) -> Option<&'a str> {
+        table.get(key).and_then(Item::as_str)
+    }
+    changes
+        .iter()
+        .enumerate()
+        .filter(|(_, table)| {
+            field(table, "message") == Some(&*change.message)
+                && field(table, "group") == Some(&*change.group)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Sets `edited = true` in the tables of the edited `changes`,
+/// `false` if the table of an edited change can't be found
+fn mark_edited(
+    tables: Option<&mut ArrayOfTables>,
+    changes: &IndexMap<Change, ChangeMeta<true>>,
+) -> bool {
+    let mut edited = changes.iter().filter(|(_, meta)| meta.edited).peekable();
+    let Some(tables) = tables else {
+        return edited.peek().is_none();
+    };
+    for (change, _) in edited {
+        let [i] = *positions(tables, change) else {
+            return false;
+        };
+        let table = tables.get_mut(i).unwrap();
+        if table.get("edited").and_then(Item::as_bool) == Some(true) {
+            continue;
+        }
+        // `edited` comes before `merged` in the canonical format
+        let merged = table.remove("merged");
+        table.insert("edited", toml_edit::value(true));
+        if let Some(merged) = merged {
+            table.insert("merged", merged);
+        }
+    }
+    true
+}
+
+/// Replaces `old` with `new` but keeps the comments and whitespace around `old`
+fn replace_value(old: &mut Item, new: Item) {
+    let (Some(old_value), Item::Value(mut new)) = (old.as_value(), new) else {
+        return;
+    };
+    *new.decor_mut() = old_value.decor().clone();
+    *old = Item::Value(new);
+}
+
+/// A table for `change` in the canonical format of the statefile
+fn canonical_table(change: Change, meta: ChangeMeta<true>) -> Result<Table> {
+    let mut state = ReleaseState::default();
+    state.changes.insert(change, meta);
+    let mut table = release_table(state)?;
+    let Some(Item::ArrayOfTables(changes)) = table.remove("changes") else {
+        unreachable!("changes are always written")
+    };
+    Ok(without_position(changes.get(0).unwrap()))
+}
+
+/// The `[unreleased]` table of a statefile that only contains `state`
+fn release_table(state: ReleaseState) -> Result<Table> {
+    let changelog = Changelog {
+        unreleased: state,
+        releases: Vec::new(),
+    };
+    let mut doc: Document = changelog.to_toml()?.parse()?;
+    let Some(Item::Table(release)) = doc.remove("unreleased") else {
+        unreachable!("the unreleased changes are always written")
+    };
+    Ok(release)
+}
+
+/// Tables without a position are written after the table that precedes them in the document,
+/// so a table appended to an array of tables ends up after the last table of the array
+fn without_position(table: &Table) -> Table {
+    let mut res = Table::new();
+    for (key, item) in table.iter() {
+        res.insert(key, item.clone());
+    }
+    res
+}
@@ -6,6 +6,13 @@ use std::hash::Hash;
 #[derive(Clone, PartialEq, Eq, Default)]
 pub struct OneOrMany<T: PartialEq + Eq + Hash, const PRETTY: bool>(pub IndexSet<T>);
 
+impl<T: PartialEq + Eq + Hash, const PRETTY: bool> OneOrMany<T, PRETTY> {
+    /// Empty lists are omitted from the statefile but kept for templates
+    pub fn is_omitted(&self) -> bool {
+        PRETTY && self.0.is_empty()
+    }
+}
+
 impl<T, const PRETTY: bool> Serialize for OneOrMany<T, PRETTY>
 where
     T: Serialize + Hash + Eq,
@@ -1,5 +1,74 @@
 use std::collections::HashMap;
 
+use anyhow::Result;
+use indexmap::IndexMap;
+
+use crate::config::{Config, ForgeKind};
+
+/// Information required to build links to the forge
+#[derive(Clone)]
+pub struct Links {
+    pub forge: ForgeKind,
+    pub web_url: String,
+    pub repo: String,
+}
+
+impl Links {
+    pub fn new(config: &Config) -> Result<Links> {
+        Ok(Links {
+            forge: config.forge,
+            web_url: config.web_url()?,
+            repo: config.repo.clone(),
+        })
+    }
+
+    fn pr_url(&self, pr: u64, repo: &str) -> String {
+        let web_url = &self.web_url;
+        match self.forge {
+            ForgeKind::GitHub => format!("{web_url}/{repo}/pull/{pr}"),
+            ForgeKind::GitLab => format!("{web_url}/{repo}/-/merge_requests/{pr}"),
+            ForgeKind::Gitea => format!("{web_url}/{repo}/pulls/{pr}"),
+        }
+    }
+
+    fn md_pr_link(&self, pr: u64, repo: &str) -> String {
+        let prefix = match self.forge {
+            ForgeKind::GitLab => '!',
+            ForgeKind::GitHub | ForgeKind::Gitea => '#',
+        };
+        format!("[{prefix}{pr}]({})", self.pr_url(pr, repo))
+    }
+
+    fn issue_url(&self, issue: u64, repo: &str) -> String {
+        let web_url = &self.web_url;
+        match self.forge {
+            ForgeKind::GitHub | ForgeKind::Gitea => format!("{web_url}/{repo}/issues/{issue}"),
+            ForgeKind::GitLab => format!("{web_url}/{repo}/-/issues/{issue}"),
+        }
+    }
+
+    fn md_issue_link(&self, issue: u64, repo: &str) -> String {
+        format!("[#{issue}]({})", self.issue_url(issue, repo))
+    }
+
+    fn profile_url(&self, login: &str) -> String {
+        format!("{}/{login}", self.web_url)
+    }
+
+    fn commit_url(&self, commit: &str, repo: &str) -> String {
+        let web_url = &self.web_url;
+        match self.forge {
+            ForgeKind::GitHub | ForgeKind::Gitea => format!("{web_url}/{repo}/commit/{commit}"),
+            ForgeKind::GitLab => format!("{web_url}/{repo}/-/commit/{commit}"),
+        }
+    }
+
+    fn md_commit_link(&self, commit: &str, repo: &str) -> String {
+        let short = commit.get(..7).unwrap_or(commit);
+        format!("[`{short}`]({})", self.commit_url(commit, repo))
+    }
+}
+
 /// Filter for making the first character of a string uppercase.
 pub fn upper_first_filter(
     value: &tera::Value,
@@ -14,68 +83,145 @@ pub fn upper_first_filter(
     Ok(tera::to_value(&s)?)
 }
 
-pub fn make_pr_url(repo: String) -> impl tera::Function {
+pub fn make_pr_url(links: Links) -> impl tera::Function {
     Box::new(
         move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
-            let repo = extract_repo(&args, &repo)?;
-            let pr = extract_pr_from_args(args)?;
-            let url = pr_url(pr, &repo);
+            let repo = extract_repo(args, &links.repo)?;
+            let pr = extract_number_from_args(args, "pr")?;
+            let url = links.pr_url(pr, &repo);
             Ok(tera::to_value(url)?)
         },
     )
 }
 
-pub fn make_pr_list_md(repo: String) -> impl tera::Function {
+pub fn make_pr_list_md(links: Links) -> impl tera::Function {
     Box::new(
         move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
-            let repo = extract_repo(args, &repo)?;
-            let prs = match args.get("pr") {
-                Some(val) => match tera::from_value::<Vec<tera::Value>>(val.clone()) {
-                    Ok(val) => val,
-                    Err(_) => return Err(
-                        "argument 'pr' must be a a list of numbers (optionally prefixed with #)"
-                            .into(),
-                    ),
-                },
-                None => return Err("required argument 'pr' is missing".into()),
-            };
+            let repo = extract_repo(args, &links.repo)?;
+            let res = md_list(args, "pr", |pr| links.md_pr_link(pr, &repo))?;
+            Ok(tera::to_value(res)?)
+        },
+    )
+}
 
-            let mut res = String::new();
-
-            if let [first, rem @ ..] = &*prs {
-                let add_pr = |res: &mut String, val| -> tera::Result<()> {
-                    let pr = extract_pr(val)?;
-                    let link = md_pr_link(pr, &repo);
-                    res.push_str(&link);
-                    Ok(())
-                };
-
-                res.push('(');
-                add_pr(&mut res, first)?;
-                for pr in rem {
-                    res.push_str(", ");
-                    add_pr(&mut res, pr)?;
-                }
-                res.push(')');
-            }
+pub fn make_pr_md_link(links: Links) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let repo = extract_repo(args, &links.repo)?;
+            let pr = extract_number_from_args(args, "pr")?;
+            let url = links.md_pr_link(pr, &repo);
+            Ok(tera::to_value(url)?)
+        },
+    )
+}
 
+pub fn make_commit_url(links: Links) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let repo = extract_repo(args, &links.repo)?;
+            let commit = extract_commit_from_args(args)?;
+            let url = links.commit_url(&commit, &repo);
+            Ok(tera::to_value(url)?)
+        },
+    )
+}
+
+pub fn make_commit_md_link(links: Links) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let repo = extract_repo(args, &links.repo)?;
+            let commit = extract_commit_from_args(args)?;
+            let url = links.md_commit_link(&commit, &repo);
+            Ok(tera::to_value(url)?)
+        },
+    )
+}
+
+pub fn make_issue_url(links: Links) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let repo = extract_repo(args, &links.repo)?;
+            let issue = extract_number_from_args(args, "issue")?;
+            let url = links.issue_url(issue, &repo);
+            Ok(tera::to_value(url)?)
+        },
+    )
+}
+
+pub fn make_issue_list_md(links: Links) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let repo = extract_repo(args, &links.repo)?;
+            let res = md_list(args, "issue", |issue| links.md_issue_link(issue, &repo))?;
             Ok(tera::to_value(res)?)
         },
     )
 }
 
-pub fn make_pr_md_link(repo: String) -> impl tera::Function {
+pub fn make_issue_md_link(links: Links) -> impl tera::Function {
     Box::new(
         move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
-            let repo = extract_repo(&args, &repo)?;
-            let pr = extract_pr_from_args(args)?;
-            let url = md_pr_link(pr, &repo);
+            let repo = extract_repo(args, &links.repo)?;
+            let issue = extract_number_from_args(args, "issue")?;
+            let url = links.md_issue_link(issue, &repo);
             Ok(tera::to_value(url)?)
         },
     )
 }
 
-fn extract_pr(val: &tera::Value) -> tera::Result<u64> {
+/// Links an author to their profile using their display name,
+/// authors without an account (that are only known by name) are not linked
+pub fn make_author_link(links: Links, names: IndexMap<String, String>) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, tera::Value>| -> tera::Result<tera::Value> {
+            let author: String = match args.get("author") {
+                Some(val) => tera::from_value(val.clone())
+                    .map_err(|_| "argument 'author' must be a string")?,
+                None => return Err("required argument 'author' is missing".into()),
+            };
+            let res = match names.get(&author) {
+                Some(name) => format!("[{name}]({})", links.profile_url(&author)),
+                None => author,
+            };
+            Ok(tera::to_value(res)?)
+        },
+    )
+}
+
+/// Formats the list of numbers in argument `arg` as a comma separated list of links in parentheses
+fn md_list(
+    args: &HashMap<String, tera::Value>,
+    arg: &str,
+    link: impl Fn(u64) -> String,
+) -> tera::Result<String> {
+    let vals = match args.get(arg) {
+        Some(val) => match tera::from_value::<Vec<tera::Value>>(val.clone()) {
+            Ok(val) => val,
+            Err(_) => {
+                return Err(format!(
+                    "argument '{arg}' must be a a list of numbers (optionally prefixed with #)"
+                )
+                .into())
+            }
+        },
+        None => return Err(format!("required argument '{arg}' is missing").into()),
+    };
+
+    let mut res = String::new();
+    if !vals.is_empty() {
+        res.push('(');
+        for (i, val) in vals.iter().enumerate() {
+            if i != 0 {
+                res.push_str(", ");
+            }
+            res.push_str(&link(extract_number(val, arg)?));
+        }
+        res.push(')');
+    }
+    Ok(res)
+}
+
+fn extract_number(val: &tera::Value, arg: &str) -> tera::Result<u64> {
     match tera::from_value::<u64>(val.clone()).ok().or_else(|| {
         let val = tera::from_value::<String>(val.clone()).ok()?;
         val.parse()
@@ -83,14 +229,25 @@ fn extract_pr(val: &tera::Value) -> tera::Result<u64> {
             .or_else(|| val.strip_prefix('#')?.parse().ok())
     }) {
         Some(val) => Ok(val),
-        None => return Err("argument 'pr' must be a number (optionally prefixed with #)".into()),
+        None => {
+            Err(format!("argument '{arg}' must be a number (optionally prefixed with #)").into())
+        }
     }
 }
 
-fn extract_pr_from_args(args: &HashMap<String, tera::Value>) -> tera::Result<u64> {
-    match args.get("pr") {
-        Some(val) => extract_pr(val),
-        None => return Err("required argument 'pr' is missing".into()),
+fn extract_number_from_args(args: &HashMap<String, tera::Value>, arg: &str) -> tera::Result<u64> {
+    match args.get(arg) {
+        Some(val) => extract_number(val, arg),
+        None => Err(format!("required argument '{arg}' is missing").into()),
+    }
+}
+
+fn extract_commit_from_args(args: &HashMap<String, tera::Value>) -> tera::Result<String> {
+    match args.get("commit") {
+        Some(val) => {
+            tera::from_value(val.clone()).map_err(|_| "argument 'commit' must be a string".into())
+        }
+        None => Err("required argument 'commit' is missing".into()),
     }
 }
 
@@ -102,11 +259,3 @@ fn extract_repo(args: &HashMap<String, tera::Value>, default: &str) -> tera::Res
         None => Ok(default.to_owned()),
     }
 }
-
-fn pr_url(pr: u64, repo: &str) -> String {
-    format!("https://github.com/{repo}/pull/{pr}")
-}
-
-fn md_pr_link(pr: u64, repo: &str) -> String {
-    format!("[#{pr}]({})", pr_url(pr, repo))
-}
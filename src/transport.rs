@@ -0,0 +1,305 @@
+use std::cell::Cell;
+use std::fs;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Sends requests to a forge API
+pub trait Transport {
+    /// Sends `request` (with `body` if provided) to the `api` of a forge once.
+    /// Responses with an error status are returned like any other response.
+    fn send_once(&self, request: &ureq::Request, body: Option<&str>) -> Result<Response>;
+
+    /// Waits before retrying a request or until a rate limit resets
+    fn wait(&self, duration: Duration) {
+        sleep(duration)
+    }
+
+    /// Sends `request` (with `body` if provided) to the `api` of a forge.
+    /// Transient failures (server errors and transport errors) are retried with
+    /// exponential backoff. Requests rejected by a rate limit are retried once the
+    /// rate limit resets.
+    fn send(
+        &self,
+        config: &Config,
+        api: &str,
+        request: &ureq::Request,
+        body: Option<&str>,
+    ) -> Result<Response> {
+        let limits = &config.api_limits;
+        let max_wait = Duration::from_secs(limits.max_rate_limit_wait_secs);
+        let mut backoff = Duration::from_millis(limits.initial_backoff_ms);
+        let mut retries = 0;
+        loop {
+            let res = match self.send_once(request, body) {
+                Ok(res) if res.status < 400 => return Ok(res),
+                res => res,
+            };
+
+            let wait = match &res {
+                Ok(res) if matches!(res.status, 403 | 429) => {
+                    rate_limit_reset(res).filter(|&wait| wait <= max_wait)
+                }
+                Ok(res) if !matches!(res.status, 500 | 502 | 503 | 504) => None,
+                // requests that can't be replayed are not retried
+                Err(err) if !err.is::<ureq::Transport>() => None,
+                // server errors and transport errors
+                _ => {
+                    let wait = backoff;
+                    backoff *= 2;
+                    Some(wait)
+                }
+            };
+
+            match wait {
+                Some(wait) if retries < limits.max_retries => {
+                    retries += 1;
+                    eprintln!(
+                        "{api} api call failed, retrying in {}s ({retries}/{})",
+                        wait.as_secs_f32(),
+                        limits.max_retries
+                    );
+                    self.wait(wait)
+                }
+                _ => {
+                    return Err(match res {
+                        Ok(res) => {
+                            anyhow!(
+                                "{api} api call failed (status {}):\n{}",
+                                res.status,
+                                res.body
+                            )
+                        }
+                        Err(err) => err.context(format!("sending request to {api} api failed")),
+                    })
+                }
+            }
+        }
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    /// Response headers with lowercase names
+    pub headers: IndexMap<String, String>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+/// Sends requests over the network
+pub struct Http;
+
+impl Transport for Http {
+    fn send_once(&self, request: &ureq::Request, body: Option<&str>) -> Result<Response> {
+        let res = match body {
+            Some(body) => request.clone().send_string(body),
+            None => request.clone().call(),
+        };
+        let res = match res {
+            Ok(res) | Err(ureq::Error::Status(_, res)) => res,
+            Err(ureq::Error::Transport(transport)) => return Err(transport.into()),
+        };
+        let headers = res
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = res.header(&name)?.to_owned();
+                Some((name.to_lowercase(), value))
+            })
+            .collect();
+        let status = res.status();
+        let body = res.into_string()?;
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// A recorded request and the response to it
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    request: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_body: Option<serde_json::Value>,
+    #[serde(default = "ok", skip_serializing_if = "is_ok")]
+    status: u16,
+    #[serde(default)]
+    headers: IndexMap<String, String>,
+    /// The response body, stored as JSON (if possible) to keep fixtures readable
+    response: serde_json::Value,
+}
+
+fn ok() -> u16 {
+    200
+}
+
+fn is_ok(status: &u16) -> bool {
+    *status == 200
+}
+
+/// Sends requests over the network and records the responses
+/// to numbered fixture files in `dir`
+pub struct Record {
+    pub dir: PathBuf,
+    pub next: Cell<usize>,
+}
+
+impl Transport for Record {
+    fn send_once(&self, request: &ureq::Request, body: Option<&str>) -> Result<Response> {
+        let res = Http.send_once(request, body)?;
+        let fixture = Fixture {
+            request: format!("{} {}", request.method(), request.url()),
+            request_body: body.map(to_json),
+            status: res.status,
+            headers: res.headers.clone(),
+            response: to_json(&res.body),
+        };
+        fs::create_dir_all(&self.dir)?;
+        let path = fixture_path(&self.dir, &self.next);
+        fs::write(&path, serde_json::to_string_pretty(&fixture)?)
+            .with_context(|| format!("failed to write fixture {}", path.display()))?;
+        Ok(res)
+    }
+}
+
+/// Replays the responses recorded by [`Record`] (in the same order)
+/// instead of accessing the network. Fails if a request differs from the recorded request.
+pub struct Replay {
+    pub dir: PathBuf,
+    pub next: Cell<usize>,
+}
+
+impl Transport for Replay {
+    fn send_once(&self, request: &ureq::Request, body: Option<&str>) -> Result<Response> {
+        let path = fixture_path(&self.dir, &self.next);
+        let fixture = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "no recorded response for {} {} ({} not found)",
+                request.method(),
+                request.url(),
+                path.display()
+            )
+        })?;
+        let fixture: Fixture = serde_json::from_str(&fixture)?;
+        let request = format!("{} {}", request.method(), request.url());
+        let request_body = body.map(to_json);
+        if fixture.request != request || fixture.request_body != request_body {
+            bail!(
+                "request does not match the recorded request in {} \
+                 (record the fixtures again with --record-fixtures):\n{}",
+                path.display(),
+                diff(
+                    &request_lines(&fixture.request, fixture.request_body.as_ref()),
+                    &request_lines(&request, request_body.as_ref())
+                )
+            )
+        }
+        let body = match fixture.response {
+            serde_json::Value::String(body) => body,
+            body => body.to_string(),
+        };
+        Ok(Response {
+            status: fixture.status,
+            headers: fixture.headers,
+            body,
+        })
+    }
+
+    /// Recorded responses are available immediately
+    fn wait(&self, _duration: Duration) {}
+}
+
+/// Bodies are stored as JSON (if possible) to keep fixtures readable
+fn to_json(src: &str) -> serde_json::Value {
+    serde_json::from_str(src).unwrap_or_else(|_| serde_json::Value::String(src.to_owned()))
+}
+
+/// Splits a request into lines to show how it differs from the recorded request.
+/// GraphQL queries are sent on a single line, so the body is split where the
+/// query was indented.
+fn request_lines(request: &str, body: Option<&serde_json::Value>) -> Vec<String> {
+    let mut lines = vec![request.to_owned()];
+    if let Some(body) = body {
+        let body = match body {
+            serde_json::Value::String(body) => body.clone(),
+            body => body.to_string(),
+        };
+        lines.extend(
+            body.split("  ")
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned),
+        );
+    }
+    lines
+}
+
+/// A line diff of `expected` and `found` (based on their longest common subsequence)
+fn diff(expected: &[String], found: &[String]) -> String {
+    // lcs[i][j] is the length of the longest common subsequence of expected[i..] and found[j..]
+    let mut lcs = vec![vec![0; found.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..found.len()).rev() {
+            lcs[i][j] = if expected[i] == found[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut res = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < found.len() {
+        if i < expected.len() && j < found.len() && expected[i] == found[j] {
+            format_to!(res, "  {}\n", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == found.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            format_to!(res, "- {}\n", expected[i]);
+            i += 1;
+        } else {
+            format_to!(res, "+ {}\n", found[j]);
+            j += 1;
+        }
+    }
+    res
+}
+
+fn fixture_path(dir: &std::path::Path, next: &Cell<usize>) -> PathBuf {
+    let i = next.get();
+    next.set(i + 1);
+    dir.join(format!("{i:03}.json"))
+}
+
+/// Returns how long to wait until a request that was rejected
+/// by a rate limit can be retried (if the response is a rate limit)
+fn rate_limit_reset(response: &Response) -> Option<Duration> {
+    if let Some(retry_after) = response.header("retry-after") {
+        return retry_after.parse().ok().map(Duration::from_secs);
+    }
+    if response.header("x-ratelimit-remaining") == Some("0") {
+        let reset: i64 = response.header("x-ratelimit-reset")?.parse().ok()?;
+        let wait = reset - Utc::now().timestamp();
+        // add a second of leeway to account for clock skew
+        return Some(Duration::from_secs(wait.max(0) as u64 + 1));
+    }
+    None
+}
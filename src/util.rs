@@ -5,3 +5,58 @@ macro_rules! format_to {
         { use ::std::fmt::Write as _; let _ = ::std::write!($buf, $lit $($arg)*); }
     };
 }
+
+/// FNV-1a hash, unlike the std hashers this hash is stable across Rust versions
+/// so it can be persisted
+pub fn stable_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the number of single character insertions, deletions and substitutions
+/// required to turn `a` into `b` (ignoring case)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "Fixes"), 5);
+        assert_eq!(edit_distance("Fixes", ""), 5);
+        assert_eq!(edit_distance("Fixes", "Fixes"), 0);
+        // substitution, insertion and deletion
+        assert_eq!(edit_distance("Fixes", "Fixed"), 1);
+        assert_eq!(edit_distance("Feature", "Features"), 1);
+        assert_eq!(edit_distance("Features", "Feature"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn distance_ignores_case() {
+        assert_eq!(edit_distance("feature", "Features"), 1);
+        assert_eq!(edit_distance("BREAKING CHANGES", "Breaking changes"), 0);
+    }
+
+    #[test]
+    fn distance_counts_chars() {
+        assert_eq!(edit_distance("Übersetzungen", "Ubersetzungen"), 1);
+    }
+}
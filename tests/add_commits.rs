@@ -0,0 +1,122 @@
+//! End-to-end tests for the `add-commits` command in a temporary git repository
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command, git, stdout};
+
+/// Creates a git repository with a `v0.1.0` tag followed by `commits`
+/// (`(author, message)` pairs)
+fn repo(commits: &[(&str, &str)]) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    git(dir.path(), &["init", "-q"]);
+    git(
+        dir.path(),
+        &["commit", "-q", "--allow-empty", "-m", "initial commit"],
+    );
+    git(dir.path(), &["tag", "v0.1.0"]);
+    for (author, message) in commits {
+        git(
+            dir.path(),
+            &[
+                "commit",
+                "-q",
+                "--allow-empty",
+                "--author",
+                author,
+                "-m",
+                message,
+            ],
+        );
+    }
+    dir
+}
+
+fn add_commits(dir: &TempDir) -> String {
+    stdout(
+        command(dir)
+            .current_dir(dir.path())
+            .args(["add-commits", "v0.1.0"])
+            .output()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn add_commit_changes() {
+    let dir = repo(&[
+        (
+            "Alice <12345+alice@users.noreply.github.com>",
+            "Add workspace symbol picker\n\nchangelog[Features]: Add workspace symbol picker",
+        ),
+        (
+            "Bob <bob@example.com>",
+            "fix panic\n\nchangelog[Fixes]: Fix panic when closing the last buffer\n\nCo-authored-by: Carol <carol@example.com>",
+        ),
+    ]);
+    let output = add_commits(&dir);
+    assert_eq!(output, "statefile not found, generating a new release\n");
+    let hashes = git(
+        dir.path(),
+        &["log", "--reverse", "--format=%H", "v0.1.0..HEAD"],
+    );
+    let hashes: Vec<_> = hashes.lines().collect();
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    assert_eq!(
+        state,
+        format!(
+            "\
+[[unreleased.changes]]
+message = 'Add workspace symbol picker'
+group = 'Features'
+commit = '{}'
+author = 'alice'
+generated = '8845cd3c'
+
+[[unreleased.changes]]
+message = 'Fix panic when closing the last buffer'
+group = 'Fixes'
+commit = '{}'
+author_name = [
+    'Bob',
+    'Carol',
+]
+generated = 'bb2b6590'
+",
+            hashes[0], hashes[1]
+        )
+    );
+}
+
+#[test]
+fn ignored_commit_authors() {
+    let dir = repo(&[
+        // matched by the login in the noreply email
+        (
+            "Dependabot <49699333+dependabot@users.noreply.github.com>",
+            "Bump serde",
+        ),
+        // matched by the name
+        ("dependabot <bot@example.com>", "Bump tera"),
+        ("renovate[bot] <bot@renovateapp.com>", "Update regex"),
+    ]);
+    let output = add_commits(&dir);
+    let hashes = git(
+        dir.path(),
+        &["log", "--reverse", "--format=%H", "v0.1.0..HEAD"],
+    );
+    let hashes: Vec<_> = hashes.lines().collect();
+    assert_eq!(
+        output,
+        format!(
+            "\
+statefile not found, generating a new release
+skipping commit {}: authored by the ignored author `dependabot`
+skipping commit {}: authored by the ignored author `dependabot`
+skipping commit {}: authored by the bot `renovate[bot]`
+",
+            hashes[0], hashes[1], hashes[2]
+        )
+    );
+}
@@ -0,0 +1,387 @@
+//! End-to-end tests that ingest PRs from recorded GitHub API responses
+//! (see `--record-fixtures`) and render the resulting changelog.
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{crabby_changes, run, stdout};
+
+#[test]
+fn add_pr_by_number() {
+    let dir = TempDir::new().unwrap();
+    let output = crabby_changes(&dir, "add_pr", &["add-pr", "4012", "4013"]);
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    stdout(output);
+    // the commits of #4013 are only partially retrieved
+    assert!(
+        stderr.contains(
+            "warning: PR #4013: has more than 100 commits, \
+            co-authors of the remaining commits are not credited"
+        ),
+        "{stderr}"
+    );
+    let changelog = stdout(crabby_changes(&dir, "add_pr", &["render"]));
+    // alice created #3990 before #4012 but it was merged later,
+    // bob created #4013 after #1180 and #1203 which were merged first
+    assert_eq!(
+        changelog.trim_end(),
+        "\
+Thanks to alice, carol, Dave Doe, bob!
+@alice made their first contribution in [#4012](https://github.com/helix-editor/helix/pull/4012)
+
+Breaking changes:
+- `space + S` now opens the workspace symbol picker ([#4012](https://github.com/helix-editor/helix/pull/4012)) by [Alice Liddell](https://github.com/alice), [carol](https://github.com/carol), Dave Doe
+
+Features:
+- Add workspace symbol picker ([#4012](https://github.com/helix-editor/helix/pull/4012)) by [Alice Liddell](https://github.com/alice), [carol](https://github.com/carol), Dave Doe
+
+Fixes:
+- Fix panic when closing the last buffer ([#4013](https://github.com/helix-editor/helix/pull/4013)), fixes [#3999](https://github.com/helix-editor/helix/issues/3999) by [Bob](https://github.com/bob)"
+    );
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    // every change records when and by which commit its PR was merged
+    assert!(
+        state.contains(
+            "\
+author = 'bob'
+generated = 'bb2b6590'
+merged = [
+    { pr = 4013, merged_at = '2022-10-13T12:00:00Z', \
+merge_commit = '0000000000000000000000000000000001e4e883', \
+url = 'https://github.com/helix-editor/helix/pull/4013' },
+]
+"
+        ),
+        "{state}"
+    );
+}
+
+#[test]
+fn add_pr_merged_since() {
+    let dir = TempDir::new().unwrap();
+    stdout(crabby_changes(
+        &dir,
+        "add_pr_since",
+        &["add-pr", "--since-timestamp", "1660000000"],
+    ));
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    // PRs from both pages are ingested, PRs from ignored authors are skipped
+    assert!(state.contains("message = 'Fix panic when closing the last buffer'"));
+    assert!(state.contains("message = 'Update rust grammar'"));
+    assert!(!state.contains("tree-sitter"));
+    // PRs from bots are skipped with `ignore_bots`
+    assert!(!state.contains("serde"));
+}
+
+#[test]
+fn add_pr_split_search_window() {
+    let dir = TempDir::new().unwrap();
+    // the first search reports more than 1000 PRs, so the window is split in half
+    // and the PRs of both halves (which overlap at the boundary) are merged
+    let args = [
+        "add-pr",
+        "--since-timestamp",
+        "1660000000",
+        "--until-timestamp",
+        "1670000000",
+    ];
+    stdout(crabby_changes(&dir, "add_pr_split", &args));
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    let (changes, _) = state.split_once("[unreleased.author_names]").unwrap();
+    let prs: Vec<_> = changes
+        .lines()
+        .filter(|line| line.starts_with("pr = "))
+        .collect();
+    assert_eq!(
+        prs,
+        ["pr = 4012", "pr = 4012", "pr = 4013", "pr = 4021"],
+        "{state}"
+    );
+}
+
+#[test]
+fn add_pr_until_requires_since() {
+    let dir = TempDir::new().unwrap();
+    let output = run(&dir, &["add-pr", "--until-timestamp", "1670000000", "4012"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("<--since-timestamp <SINCE_TIMESTAMP>|--since-ref <SINCE_REF>>"),
+        "{stderr}"
+    );
+    assert!(!dir.path().join("changelog.toml").exists());
+}
+
+#[test]
+fn add_pr_retry() {
+    let dir = TempDir::new().unwrap();
+    // the first response is a 502 and the rate limit is exhausted afterwards
+    let output = crabby_changes(&dir, "add_pr_retry", &["add-pr", "4013"]);
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    stdout(output);
+    assert_eq!(
+        stderr,
+        "\
+github api call failed, retrying in 1s (1/5)
+github api rate limit exhausted, waiting 1s until it resets
+warning: PR #4013: has more than 100 commits, co-authors of the remaining commits are not credited
+github api rate limit exhausted, waiting 1s until it resets
+"
+    );
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    assert!(state.contains("message = 'Fix panic when closing the last buffer'"));
+}
+
+#[test]
+fn add_pr_not_found() {
+    let dir = TempDir::new().unwrap();
+    let output = crabby_changes(&dir, "add_pr_not_found", &["add-pr", "5"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("PR #5 not found in helix-editor/helix"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn add_pr_sync() {
+    let dir = TempDir::new().unwrap();
+    let state_file = dir.path().join("changelog.toml");
+    stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    // edit one of the generated changes by hand
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    let state = state.replace(
+        "`space + S` opens the workspace symbol picker now",
+        "`space + S` opens the workspace symbol picker instead of the document symbol picker",
+    );
+    std::fs::write(&state_file, state).unwrap();
+
+    // the PR description was updated in the meantime
+    let output = stdout(crabby_changes(
+        &dir,
+        "add_pr_sync",
+        &["add-pr", "--sync", "4012"],
+    ));
+    assert_eq!(
+        output,
+        "PR #4012: not adding [Breaking changes] `space + S` now opens the workspace symbol picker \
+         in place of [Breaking changes] `space + S` opens the workspace symbol picker instead of \
+         the document symbol picker, which was edited by hand\n"
+    );
+    let changelog = stdout(crabby_changes(&dir, "add_pr_sync", &["render"]));
+    assert!(
+        changelog.contains(
+            "\
+Breaking changes:
+- `space + S` opens the workspace symbol picker instead of the document symbol picker ([#4012](https://github.com/helix-editor/helix/pull/4012))"
+        ),
+        "{changelog}"
+    );
+    assert!(
+        changelog.contains(
+            "\
+Features:
+- Add workspace symbol picker ([#4012](https://github.com/helix-editor/helix/pull/4012))"
+        ),
+        "{changelog}"
+    );
+    assert!(!changelog.contains("Add a picker for workspace symbols"));
+    assert!(!changelog.contains("`space + S` now opens"));
+}
+
+#[test]
+fn add_pr_sync_legacy() {
+    let dir = TempDir::new().unwrap();
+    let state_file = dir.path().join("changelog.toml");
+    // written by a version that didn't record which changes were generated
+    let legacy = "\
+[[unreleased.changes]]
+message = 'Add workspace symbol picker'
+group = 'Features'
+pr = 4012
+
+[[unreleased.changes]]
+message = '`space + S` opens the workspace symbol picker now'
+group = 'Breaking changes'
+pr = 4012
+
+[unreleased.new_contributors]
+alice = 4012
+";
+    std::fs::write(&state_file, legacy).unwrap();
+    let output = stdout(crabby_changes(
+        &dir,
+        "add_pr_sync",
+        &["add-pr", "--sync", "4012"],
+    ));
+    assert_eq!(
+        output,
+        "PR #4012: not adding [Breaking changes] `space + S` now opens the workspace symbol picker \
+         in place of [Breaking changes] `space + S` opens the workspace symbol picker now, \
+         which has no `generated` hash (it was added by hand or by an older version)\n"
+    );
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    // the unchanged change is adopted, the other one is kept without adding a duplicate,
+    // both record the merge of the PR
+    assert_eq!(
+        state,
+        "\
+[[unreleased.changes]]
+message = 'Add workspace symbol picker'
+group = 'Features'
+pr = 4012
+author = [
+    'alice',
+    'carol',
+]
+author_name = 'Dave Doe'
+generated = '8845cd3c'
+merged = [
+    { pr = 4012, merged_at = '2022-10-12T12:00:00Z', \
+merge_commit = '0000000000000000000000000000000001e4c994', \
+url = 'https://github.com/helix-editor/helix/pull/4012' },
+]
+
+[[unreleased.changes]]
+message = '`space + S` opens the workspace symbol picker now'
+group = 'Breaking changes'
+pr = 4012
+merged = [
+    { pr = 4012, merged_at = '2022-10-12T12:00:00Z', \
+merge_commit = '0000000000000000000000000000000001e4c994', \
+url = 'https://github.com/helix-editor/helix/pull/4012' },
+]
+
+[unreleased.author_names]
+alice = 'Alice Liddell'
+carol = 'carol'
+
+[unreleased.new_contributors]
+alice = 4012
+"
+    );
+}
+
+#[test]
+fn add_pr_preserves_edits() {
+    let dir = TempDir::new().unwrap();
+    let state_file = dir.path().join("changelog.toml");
+    let original = "`space + S` opens the workspace symbol picker now";
+    let edited =
+        "`space + S` opens the workspace symbol picker instead of the document symbol picker";
+    stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    std::fs::write(&state_file, state.replace(original, edited)).unwrap();
+
+    // adding the PR again must not bring back the original text
+    stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    assert!(state.contains(edited), "{state}");
+    assert!(!state.contains(original), "{state}");
+    assert!(state.contains("edited = true"), "{state}");
+
+    // edits stay marked even if they are reverted
+    std::fs::write(&state_file, state.replace(edited, original)).unwrap();
+    stdout(crabby_changes(
+        &dir,
+        "add_pr_sync",
+        &["add-pr", "--sync", "4012"],
+    ));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    assert!(state.contains(original), "{state}");
+    assert!(!state.contains("`space + S` now opens"), "{state}");
+    assert!(
+        state.contains("message = 'Add workspace symbol picker'"),
+        "{state}"
+    );
+}
+
+#[test]
+fn in_place_edits_keep_edited_flags() {
+    let dir = TempDir::new().unwrap();
+    let state_file = dir.path().join("changelog.toml");
+    let original = "`space + S` opens the workspace symbol picker now";
+    let edited =
+        "`space + S` opens the workspace symbol picker instead of the document symbol picker";
+    stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    stdout(run(&dir, &["add", "Fix panic", "--group", "Features"]));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    std::fs::write(&state_file, state.replace(original, edited)).unwrap();
+
+    // the edit is only detected when the statefile is loaded,
+    // editing another change in place must record it
+    stdout(run(&dir, &["move", "3", "--group", "Fixes"]));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    assert!(state.contains("edited = true"), "{state}");
+
+    std::fs::write(&state_file, state.replace(edited, original)).unwrap();
+    stdout(crabby_changes(
+        &dir,
+        "add_pr_sync",
+        &["add-pr", "--sync", "4012"],
+    ));
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    assert!(state.contains(original), "{state}");
+    assert!(!state.contains("`space + S` now opens"), "{state}");
+}
+
+#[test]
+fn add_pr_removed_change() {
+    let dir = TempDir::new().unwrap();
+    let state_file = dir.path().join("changelog.toml");
+    stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    let output = stdout(run(&dir, &["remove", "1"]));
+    assert_eq!(
+        output,
+        "removed [Breaking changes] `space + S` opens the workspace symbol picker now\n"
+    );
+    let state = std::fs::read_to_string(&state_file).unwrap();
+    assert!(
+        state.ends_with(
+            "
+[[unreleased.removed]]
+pr = 4012
+generated = 'e5961ec0'
+"
+        ),
+        "{state}"
+    );
+
+    // adding the PR again must not bring back the removed change
+    let output = stdout(crabby_changes(&dir, "add_pr_sync_old", &["add-pr", "4012"]));
+    assert_eq!(
+        output,
+        "PR #4012: not adding [Breaking changes] `space + S` opens the workspace symbol picker now, \
+         which was removed by hand\n"
+    );
+    assert_eq!(std::fs::read_to_string(&state_file).unwrap(), state);
+}
+
+#[test]
+fn add_pr_no_data() {
+    let dir = TempDir::new().unwrap();
+    let output = crabby_changes(&dir, "add_pr_no_data", &["add-pr", "4013"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("github api response contained neither data nor errors"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn replay_mismatched_request() {
+    let dir = TempDir::new().unwrap();
+    // the fixture was recorded for PR #5
+    let output = crabby_changes(&dir, "add_pr_not_found", &["add-pr", "4012"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("request does not match the recorded request"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("- pullRequest(number: 5) {"), "{stderr}");
+    assert!(stderr.contains("+ pullRequest(number: 4012) {"), "{stderr}");
+}
@@ -0,0 +1,107 @@
+//! End-to-end tests for archiving releases in the statefile
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command_with_config, fixtures, run, state, state_dir, stdout};
+
+/// Renders the changelog with a template that shows the version and date of the releases
+fn render(dir: &TempDir, args: &[&str]) -> String {
+    let output = command_with_config(dir, &fixtures().join("archive.toml"))
+        .arg("render")
+        .args(args)
+        .output()
+        .unwrap();
+    stdout(output)
+}
+
+#[test]
+fn archive_release() {
+    let dir = state_dir(
+        r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+pr = 4012
+"#,
+    );
+    stdout(run(&dir, &["archive", "0.2.0", "--date", "2022-10-01"]));
+    assert_eq!(
+        state(&dir),
+        "\
+[unreleased]
+changes = []
+
+[[releases]]
+version = '0.2.0'
+date = '2022-10-01'
+
+[[releases.changes]]
+message = 'Add workspace symbol picker'
+group = 'Features'
+pr = 4012
+"
+    );
+    assert_eq!(
+        render(&dir, &["0.2.0"]).trim(),
+        "## 0.2.0 (2022-10-01)\n- Add workspace symbol picker"
+    );
+
+    // the same version can't be released twice
+    assert!(!run(&dir, &["archive", "0.2.0"]).status.success());
+}
+
+#[test]
+fn render_all_releases() {
+    let dir = state_dir(
+        r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+"#,
+    );
+    stdout(run(&dir, &["archive", "0.2.0", "--date", "2022-10-01"]));
+    stdout(run(&dir, &["add", "Fix panic", "--group", "Fixes"]));
+    stdout(run(&dir, &["archive", "0.3.0", "--date", "2022-11-01"]));
+    let state = state(&dir);
+    // the most recent release comes first
+    assert!(
+        state.find("version = '0.3.0'").unwrap() < state.find("version = '0.2.0'").unwrap(),
+        "{state}"
+    );
+    assert_eq!(
+        render(&dir, &["--all"]).trim(),
+        "\
+## 0.3.0 (2022-11-01)
+- Fix panic
+
+## 0.2.0 (2022-10-01)
+- Add workspace symbol picker"
+    );
+}
+
+#[test]
+fn legacy_statefile() {
+    // statefiles used to only contain the unreleased changes
+    let dir = state_dir(
+        r#"
+authors = ["alice"]
+
+[[changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+pr = 4012
+"#,
+    );
+    assert_eq!(
+        render(&dir, &[]).trim(),
+        "## Unreleased\n- Add workspace symbol picker"
+    );
+    stdout(run(&dir, &["archive", "0.2.0", "--date", "2022-10-01"]));
+    let state = state(&dir);
+    assert!(
+        state.starts_with("[unreleased]\nchanges = []\n\n[[releases]]\nversion = '0.2.0'\ndate = '2022-10-01'\nauthors = ['alice']\n"),
+        "{state}"
+    );
+}
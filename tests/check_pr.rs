@@ -0,0 +1,121 @@
+//! End-to-end tests for validating the changelog metadata of PRs with `check-pr`
+
+mod common;
+
+use std::process::Output;
+
+use tempfile::TempDir;
+
+use common::{command_with_config, crabby_changes, fixtures, stdout};
+
+fn check_event(event: &str) -> Output {
+    let dir = TempDir::new().unwrap();
+    let events = fixtures().join("events");
+    command_with_config(&dir, &events.join("config.toml"))
+        .env("GITHUB_EVENT_PATH", events.join(format!("{event}.json")))
+        .arg("check-pr")
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: Output) -> String {
+    assert!(!output.status.success(), "check-pr unexpectedly passed");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn check_pr_by_number() {
+    let dir = TempDir::new().unwrap();
+    let output = stdout(crabby_changes(&dir, "add_pr", &["check-pr", "4012"]));
+    assert!(output.contains("[Breaking changes] `space + S` now opens the workspace symbol picker"));
+    assert!(output.contains("[Features] Add workspace symbol picker"));
+    // the changelog is not modified
+    assert!(!dir.path().join("changelog.toml").exists());
+}
+
+#[test]
+fn check_pr_event() {
+    let output = stdout(check_event("valid"));
+    assert!(output.contains("[Features] Add `:rsort` command"));
+    assert!(output.contains("[Features] Add `:sort` command"));
+}
+
+#[test]
+fn check_pr_unknown_group() {
+    let stderr = stderr(check_event("unknown_group"));
+    assert!(stderr.contains("unknown group `Typos`"), "{stderr}");
+}
+
+#[test]
+fn check_pr_unknown_group_suggestion() {
+    let stderr = stderr(check_event("unknown_group_typo"));
+    assert!(
+        stderr.contains("unknown group `feature` (did you mean `Features`?)"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn check_pr_malformed_line() {
+    let stderr = stderr(check_event("malformed"));
+    assert!(
+        stderr.contains("missing `:` in `changelog Fix crash when opening a directory`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn check_pr_no_entry() {
+    let stderr = stderr(check_event("no_entry"));
+    assert!(
+        stderr.contains("no changelog entry would be generated"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn check_pr_changelog_block() {
+    let output = stdout(check_event("block"));
+    let entries = &output[output.find("PR #4034").unwrap()..];
+    assert_eq!(
+        entries.trim_end(),
+        "\
+PR #4034 adds the following changelog entries:
+  [Breaking changes] The `sort` keybinding was removed, use
+    - `:sort` or
+    - `:rsort` instead
+  [Fixes] Fix selections being sorted by length
+  [Features] Add `:sort` command"
+    );
+}
+
+#[test]
+fn check_pr_skip_marker() {
+    let output = stdout(check_event("skip"));
+    assert!(
+        output.contains(
+            "PR #4035 will not be added to the changelog: opted out with `changelog: skip`"
+        ),
+        "{output}"
+    );
+}
+
+#[test]
+fn check_pr_ignored_body() {
+    let output = stdout(check_event("backport"));
+    assert!(
+        output.contains("description matches the ignored pattern `(?m)^Backport of #\\d+`"),
+        "{output}"
+    );
+}
+
+#[test]
+fn check_pr_bot() {
+    let output = stdout(check_event("bot"));
+    assert!(
+        output.contains(
+            "PR #4037 will not be added to the changelog: authored by the bot `dependabot[bot]`"
+        ),
+        "{output}"
+    );
+}
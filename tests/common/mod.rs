@@ -0,0 +1,99 @@
+//! Helpers shared by the end-to-end tests
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+pub fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Returns a command that runs `crabby_changes` with the test config and a state file in `dir`
+pub fn command(dir: &TempDir) -> Command {
+    command_with_config(dir, &fixtures().join("config.toml"))
+}
+
+/// Like [`command`] but with a different config
+pub fn command_with_config(dir: &TempDir, config: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_crabby_changes"));
+    cmd.env("GITHUB_TOKEN", "test-token")
+        .env_remove("GITHUB_EVENT_PATH")
+        .arg("--config")
+        .arg(config)
+        .arg("--state")
+        .arg(dir.path().join("changelog.toml"));
+    cmd
+}
+
+/// Runs `crabby_changes` with the forge API responses replayed from `fixtures/{replay}`
+pub fn crabby_changes(dir: &TempDir, replay: &str, args: &[&str]) -> Output {
+    command(dir)
+        .arg("--replay-fixtures")
+        .arg(fixtures().join(replay))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Like [`crabby_changes`] but with the config `fixtures/{config}`
+pub fn crabby_changes_with_config(
+    dir: &TempDir,
+    config: &str,
+    replay: &str,
+    args: &[&str],
+) -> Output {
+    command_with_config(dir, &fixtures().join(config))
+        .arg("--replay-fixtures")
+        .arg(fixtures().join(replay))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs `crabby_changes` with commands that don't access a forge
+pub fn run(dir: &TempDir, args: &[&str]) -> Output {
+    command(dir).args(args).output().unwrap()
+}
+
+/// Returns a temporary directory with a state file that contains `state`
+pub fn state_dir(state: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("changelog.toml"), state).unwrap();
+    dir
+}
+
+/// Reads the state file in `dir`
+pub fn state(dir: &TempDir) -> String {
+    std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap()
+}
+
+pub fn stdout(output: Output) -> String {
+    assert!(
+        output.status.success(),
+        "crabby_changes failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Runs git in `dir` and returns its output
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {args:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,181 @@
+//! End-to-end tests for adding, removing and moving changes from the command line
+
+mod common;
+
+use common::{run, state, state_dir, stdout};
+
+const STATE: &str = r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+pr = 4012
+
+[[unreleased.changes]]
+message = "Fix panic when closing the last buffer"
+group = "Features"
+pr = 4013
+
+[[unreleased.changes]]
+message = "Update rust grammar"
+group = "Updated languages"
+pr = 4021
+"#;
+
+#[test]
+fn add_change() {
+    let dir = state_dir(STATE);
+    let args = [
+        "add",
+        "Remove the `:sort` command",
+        "--group",
+        "Breaking changes",
+        "--pr",
+        "4030",
+        "--pr",
+        "4031",
+    ];
+    let output = stdout(run(&dir, &args));
+    assert_eq!(
+        output,
+        "added [Breaking changes] Remove the `:sort` command\n"
+    );
+    // the new change is appended in the canonical format, the rest of the file is kept
+    assert_eq!(
+        state(&dir),
+        format!(
+            "{STATE}
+[[unreleased.changes]]
+message = 'Remove the `:sort` command'
+group = 'Breaking changes'
+pr = [
+    4030,
+    4031,
+]
+"
+        )
+    );
+}
+
+#[test]
+fn add_change_unknown_group() {
+    let dir = state_dir(STATE);
+    let args = ["add", "Add foo", "--group", "Feature"];
+    let output = run(&dir, &args);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unknown group `Feature` (did you mean `Features`?)"),
+        "{stderr}"
+    );
+    assert_eq!(state(&dir), STATE);
+}
+
+#[test]
+fn remove_change() {
+    let dir = state_dir(STATE);
+    let output = stdout(run(&dir, &["remove", "#4012", "3"]));
+    assert_eq!(
+        output,
+        "\
+removed [Features] Add workspace symbol picker
+removed [Updated languages] Update rust grammar
+"
+    );
+    assert_eq!(
+        state(&dir),
+        r#"
+[[unreleased.changes]]
+message = "Fix panic when closing the last buffer"
+group = "Features"
+pr = 4013
+"#
+    );
+}
+
+#[test]
+fn move_change() {
+    let dir = state_dir(STATE);
+    let output = stdout(run(&dir, &["move", "2", "--group", "Fixes"]));
+    assert_eq!(
+        output,
+        "moved Fix panic when closing the last buffer from [Features] to [Fixes]\n"
+    );
+    // the position of the change is preserved
+    assert_eq!(
+        state(&dir),
+        STATE.replace(
+            "message = \"Fix panic when closing the last buffer\"\ngroup = \"Features\"",
+            "message = \"Fix panic when closing the last buffer\"\ngroup = \"Fixes\"",
+        )
+    );
+}
+
+const FORMATTED_STATE: &str = r#"# Changes for the next release
+[unreleased]
+
+[[unreleased.changes]]
+message = "Add workspace symbol picker" # from the PR title
+group   = "Features"
+pr      = [4012]
+
+# keep this one at the top
+[[unreleased.changes]]
+message = "Fix panic when closing the last buffer"
+group = "Features"  # wrong group
+pr = 4013
+
+[[unreleased.changes]]
+message = "Update rust grammar"
+group = "Updated languages"
+pr = 4021
+generated = "5ab04c1e"
+"#;
+
+#[test]
+fn edit_keeps_formatting() {
+    let dir = state_dir(FORMATTED_STATE);
+    stdout(run(&dir, &["move", "2", "--group", "Fixes"]));
+    assert_eq!(
+        state(&dir),
+        FORMATTED_STATE.replace(
+            "group = \"Features\"  # wrong group",
+            "group = \"Fixes\"  # wrong group"
+        )
+    );
+
+    let dir = state_dir(FORMATTED_STATE);
+    stdout(run(
+        &dir,
+        &[
+            "add",
+            "Add workspace symbol picker",
+            "--group",
+            "Features",
+            "--pr",
+            "4100",
+        ],
+    ));
+    assert_eq!(
+        state(&dir),
+        FORMATTED_STATE.replace("pr      = [4012]", "pr      = [\n    4012,\n    4100,\n]")
+    );
+
+    // removed generated changes are recorded, so that `add-pr` doesn't add them again
+    let dir = state_dir(FORMATTED_STATE);
+    stdout(run(&dir, &["remove", "3"]));
+    assert_eq!(
+        state(&dir),
+        FORMATTED_STATE.replace(
+            r#"[[unreleased.changes]]
+message = "Update rust grammar"
+group = "Updated languages"
+pr = 4021
+generated = "5ab04c1e"
+"#,
+            "[[unreleased.removed]]
+pr = 4021
+generated = '5ab04c1e'
+"
+        )
+    );
+}
@@ -0,0 +1,63 @@
+//! End-to-end tests that ingest PRs from recorded Gitea API responses
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command_with_config, crabby_changes_with_config, fixtures, stdout};
+
+fn render(dir: &TempDir) -> String {
+    let output = command_with_config(dir, &fixtures().join("gitea.toml"))
+        .arg("render")
+        .output()
+        .unwrap();
+    stdout(output)
+}
+
+#[test]
+fn add_pr_by_number() {
+    let dir = TempDir::new().unwrap();
+    // the co-author is only credited in the second page of commits
+    stdout(crabby_changes_with_config(
+        &dir,
+        "gitea.toml",
+        "gitea_pr",
+        &["add-pr", "7"],
+    ));
+    assert_eq!(
+        render(&dir),
+        "\
+Features:
+- Add workspace symbol picker ([#7](https://codeberg.org/helix-editor/helix/pulls/7)), \
+fixes [#3](https://codeberg.org/helix-editor/helix/issues/3) by alice, Dave Doe (merged 2022-10-12)
+
+
+"
+    );
+}
+
+#[test]
+fn add_prs_merged_since() {
+    let dir = TempDir::new().unwrap();
+    // #9 was closed without merging, #10 was merged into a release branch
+    // and #5 was merged before the timestamp
+    stdout(crabby_changes_with_config(
+        &dir,
+        "gitea.toml",
+        "gitea_since",
+        &["add-pr", "--since-timestamp", "1660000000"],
+    ));
+    assert_eq!(
+        render(&dir),
+        "\
+Features:
+- Add workspace symbol picker ([#7](https://codeberg.org/helix-editor/helix/pulls/7)), \
+fixes [#3](https://codeberg.org/helix-editor/helix/issues/3) by alice, Dave Doe (merged 2022-10-12)
+
+Fixes:
+- Fix panic when closing the last buffer ([#8](https://codeberg.org/helix-editor/helix/pulls/8)) by bob (merged 2022-10-13)
+
+
+"
+    );
+}
@@ -0,0 +1,67 @@
+//! End-to-end tests that ingest merge requests from recorded GitLab API responses
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command_with_config, crabby_changes_with_config, fixtures, stdout};
+
+fn render(dir: &TempDir) -> String {
+    let output = command_with_config(dir, &fixtures().join("gitlab.toml"))
+        .arg("render")
+        .output()
+        .unwrap();
+    stdout(output)
+}
+
+#[test]
+fn add_mr_by_number() {
+    let dir = TempDir::new().unwrap();
+    // the closed issues and the commits are spread over two pages,
+    // issue #3 belongs to a different project
+    let output = crabby_changes_with_config(&dir, "gitlab.toml", "gitlab_mr", &["add-pr", "12"]);
+    assert!(output.status.success());
+    // GitLab can't look up the first merged merge request of an author
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: new contributors are not detected, \
+         `detect_new_contributors` is only supported for GitHub\n"
+    );
+    assert_eq!(
+        render(&dir),
+        "\
+Features:
+- Add workspace symbol picker ([!12](https://gitlab.com/helix-editor/helix/-/merge_requests/12)), \
+fixes [#7](https://gitlab.com/helix-editor/helix/-/issues/7), \
+fixes [#8](https://gitlab.com/helix-editor/helix/-/issues/8) by alice, Dave Doe
+
+
+"
+    );
+}
+
+#[test]
+fn add_mrs_merged_since() {
+    let dir = TempDir::new().unwrap();
+    // !11 was updated after the timestamp but merged before it
+    stdout(crabby_changes_with_config(
+        &dir,
+        "gitlab.toml",
+        "gitlab_since",
+        &["add-pr", "--since-timestamp", "1660000000"],
+    ));
+    assert_eq!(
+        render(&dir),
+        "\
+Features:
+- Add workspace symbol picker ([!12](https://gitlab.com/helix-editor/helix/-/merge_requests/12)), \
+fixes [#7](https://gitlab.com/helix-editor/helix/-/issues/7), \
+fixes [#8](https://gitlab.com/helix-editor/helix/-/issues/8) by alice, Dave Doe
+
+Fixes:
+- Fix panic when closing the last buffer ([!13](https://gitlab.com/helix-editor/helix/-/merge_requests/13)) by bob
+
+
+"
+    );
+}
@@ -0,0 +1,139 @@
+//! End-to-end tests for merging changes in the statefile
+
+mod common;
+
+use common::{run, state, state_dir, stdout};
+
+const STATE: &str = r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+pr = 4012
+author = "alice"
+
+[[unreleased.changes]]
+message = "Fix panic when closing the last buffer"
+group = "Fixes"
+pr = 4013
+author = "bob"
+
+[[unreleased.changes]]
+message = "Add a workspace symbol picker."
+group = "Features"
+pr = 4015
+author = ["carol", "alice"]
+
+[[unreleased.changes]]
+message = "Add workspace symbol picker keybinding"
+group = "Features"
+pr = 4016
+author = "carol"
+"#;
+
+#[test]
+fn merge_suggest() {
+    let dir = state_dir(STATE);
+    let output = stdout(run(&dir, &["merge", "--suggest"]));
+    assert_eq!(
+        output,
+        "\
+possible duplicates in Features:
+  1: Add workspace symbol picker #4012
+  3: Add a workspace symbol picker. #4015
+  merge with `crabby_changes merge 1 3`
+"
+    );
+}
+
+#[test]
+fn merge_changes() {
+    let dir = state_dir(STATE);
+    let args = [
+        "merge",
+        "#4015",
+        "1",
+        "--message",
+        "Add a picker for workspace symbols",
+    ];
+    let output = stdout(run(&dir, &args));
+    assert_eq!(
+        output,
+        "merged changes into [Features] Add a picker for workspace symbols\n"
+    );
+    let state = state(&dir);
+    assert_eq!(
+        state,
+        "\
+[[unreleased.changes]]
+message = 'Add a picker for workspace symbols'
+group = 'Features'
+pr = [
+    4012,
+    4015,
+]
+author = [
+    'alice',
+    'carol',
+]
+
+[[unreleased.changes]]
+message = 'Fix panic when closing the last buffer'
+group = 'Fixes'
+pr = 4013
+author = 'bob'
+
+[[unreleased.changes]]
+message = 'Add workspace symbol picker keybinding'
+group = 'Features'
+pr = 4016
+author = 'carol'
+"
+    );
+}
+
+#[test]
+fn merge_invalid_change() {
+    let dir = state_dir(STATE);
+    let output = run(&dir, &["merge", "1", "9"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("there is no change 9 (there are 4 unreleased changes)"),
+        "{stderr}"
+    );
+    let output = run(&dir, &["merge", "#4012"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn merge_different_groups() {
+    let dir = state_dir(STATE);
+    let output = run(&dir, &["merge", "1", "2"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(
+            "the changes are in different groups (Features, Fixes), \
+             pass `--group` to choose the group of the merged change"
+        ),
+        "{stderr}"
+    );
+    assert_eq!(state(&dir), STATE);
+
+    let output = stdout(run(&dir, &["merge", "1", "2", "--group", "Fixes"]));
+    assert_eq!(
+        output,
+        "merged changes into [Fixes] Add workspace symbol picker\n"
+    );
+    assert!(state(&dir).starts_with(
+        "\
+[[unreleased.changes]]
+message = 'Add workspace symbol picker'
+group = 'Fixes'
+pr = [
+    4012,
+    4013,
+]
+"
+    ));
+}
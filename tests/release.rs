@@ -0,0 +1,100 @@
+//! End-to-end tests for the `release` command in a temporary git repository
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command_with_config, git, stdout};
+
+const CONFIG: &str = r#"
+main_branch = "master"
+repo = "helix-editor/helix"
+tag_prefix = "v"
+version_files = ["Cargo.toml"]
+template = """
+## {{ version }}
+{% for change in changes %}\
+- {{ change.message }}
+{% endfor %}\
+"""
+"#;
+
+const STATE: &str = r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+pr = 4012
+"#;
+
+/// Creates a git repository with a `v0.1.0` tag, a version file and a changelog
+fn repo(version_file: &str) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("config.toml"), CONFIG).unwrap();
+    std::fs::write(dir.path().join("changelog.toml"), STATE).unwrap();
+    std::fs::write(dir.path().join("Cargo.toml"), version_file).unwrap();
+    std::fs::write(
+        dir.path().join("CHANGELOG.md"),
+        "# Changelog\n\n## 0.1.0\n- Initial release\n",
+    )
+    .unwrap();
+    git(dir.path(), &["init", "-q"]);
+    git(dir.path(), &["add", "."]);
+    git(dir.path(), &["commit", "-q", "-m", "initial commit"]);
+    git(dir.path(), &["tag", "v0.1.0"]);
+    dir
+}
+
+fn release(dir: &TempDir, version: &str) -> std::process::Output {
+    command_with_config(dir, &dir.path().join("config.toml"))
+        .current_dir(dir.path())
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .args(["release", version])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn release_commit_and_tag() {
+    let dir = repo("[package]\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = \"0.1.0\"\n");
+    stdout(release(&dir, "0.2.0"));
+
+    let changelog = std::fs::read_to_string(dir.path().join("CHANGELOG.md")).unwrap();
+    assert_eq!(
+        changelog,
+        "# Changelog\n\n## 0.2.0\n- Add workspace symbol picker\n\n## 0.1.0\n- Initial release\n"
+    );
+    let version_file = std::fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+    // the dependency with the same version is not bumped
+    assert_eq!(
+        version_file,
+        "[package]\nversion = \"0.2.0\"\n\n[dependencies]\nfoo = \"0.1.0\"\n"
+    );
+    assert_eq!(git(dir.path(), &["tag", "-l", "v0.2.0"]), "v0.2.0\n");
+    assert_eq!(
+        git(dir.path(), &["log", "-1", "--format=%s"]),
+        "chore: release 0.2.0\n"
+    );
+    // the changelog, the version files and the statefile are part of the release commit
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+
+    let state = std::fs::read_to_string(dir.path().join("changelog.toml")).unwrap();
+    assert!(state.contains("[[releases]]\nversion = '0.2.0'"), "{state}");
+}
+
+#[test]
+fn release_missing_version() {
+    let dir = repo("[package]\nversion = \"0.0.1\"\n");
+    let output = release(&dir, "0.2.0");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("version 0.1.0 not found in Cargo.toml"),
+        "{stderr}"
+    );
+    // no file was modified
+    assert_eq!(git(dir.path(), &["status", "--porcelain"]), "");
+    assert_eq!(git(dir.path(), &["tag", "-l"]), "v0.1.0\n");
+}
@@ -0,0 +1,54 @@
+//! End-to-end tests for rendering the changelog from a statefile
+
+mod common;
+
+use tempfile::TempDir;
+
+use common::{command, stdout};
+
+fn render(state: &str, args: &[&str]) -> String {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("changelog.toml"), state).unwrap();
+    stdout(command(&dir).arg("render").args(args).output().unwrap())
+}
+
+#[test]
+fn render_without_authors() {
+    // empty lists are omitted from the statefile but must be available in templates
+    let state = r#"
+[[unreleased.changes]]
+message = "Add workspace symbol picker"
+group = "Features"
+"#;
+    assert_eq!(
+        render(state, &[]).trim(),
+        "\
+Thanks to !
+
+Features:
+- Add workspace symbol picker  by"
+    );
+}
+
+#[test]
+fn render_multi_line_message() {
+    let state = r#"
+[[unreleased.changes]]
+message = """
+The `sort` keybinding was removed, use
+- `:sort` or
+- `:rsort` instead"""
+group = "Breaking changes"
+author = "alice"
+"#;
+    assert_eq!(
+        render(state, &[]).trim(),
+        "\
+Thanks to alice!
+
+Breaking changes:
+- The `sort` keybinding was removed, use
+  - `:sort` or
+  - `:rsort` instead  by alice"
+    );
+}